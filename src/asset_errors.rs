@@ -0,0 +1,64 @@
+use anyhow::Result;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+pub struct AssetErrorsPlugin;
+
+impl Plugin for AssetErrorsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<AssetErrors>()
+            .add_system(ui_asset_errors.system());
+    }
+}
+
+/// The last error for every asset file that failed to load, shared with the asset loaders.
+#[derive(Clone, Default)]
+pub struct AssetErrors(Arc<Mutex<BTreeMap<PathBuf, String>>>);
+
+impl AssetErrors {
+    pub fn report(&self, path: &Path, result: &Result<()>) {
+        let mut errors = self.0.lock().unwrap();
+        match result {
+            Ok(()) => {
+                errors.remove(path);
+            }
+            Err(e) => {
+                eprintln!("error: could not load {}: {:#}", path.display(), e);
+                errors.insert(path.to_path_buf(), format!("{:#}", e));
+            }
+        }
+    }
+
+    /// Every error, after the path of its file, for tools that have no window to show
+    /// them in.
+    pub fn messages(&self) -> Vec<String> {
+        let errors = self.0.lock().unwrap();
+        errors
+            .iter()
+            .map(|(path, error)| format!("{}: {}", path.display(), error))
+            .collect()
+    }
+}
+
+fn ui_asset_errors(egui_context: Res<EguiContext>, asset_errors: Res<AssetErrors>) {
+    let errors = asset_errors.0.lock().unwrap();
+    if errors.is_empty() {
+        return;
+    }
+
+    egui::Window::new("Asset errors")
+        .anchor(egui::Align2::CENTER_TOP, [0., 10.])
+        .collapsible(false)
+        .resizable(false)
+        .show(egui_context.ctx(), |ui| {
+            for (path, error) in errors.iter() {
+                ui.colored_label(egui::Color32::RED, path.display().to_string());
+                ui.label(error);
+            }
+        });
+}
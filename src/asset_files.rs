@@ -0,0 +1,169 @@
+use anyhow::{bail, Result};
+use bevy::{
+    asset::{Asset, LoadContext, LoadedAsset},
+    prelude::*,
+};
+use serde::{
+    de::{self, value::MapAccessDeserializer, DeserializeOwned, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{fmt, marker::PhantomData};
+
+/// Splits a `"file#label"` reference into its file and label parts.
+pub(crate) fn split_reference(reference: &str) -> (&str, Option<&str>) {
+    match reference.split_once('#') {
+        Some((file, label)) => (file, Some(label)),
+        None => (reference, None),
+    }
+}
+
+/// The contents of a file holding either a single entry, or a list of labeled ones.
+#[derive(Debug, Clone)]
+pub(crate) enum Entries<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T> Entries<T> {
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        match self {
+            Entries::One(entry) => std::slice::from_ref(entry).iter(),
+            Entries::Many(entries) => entries.iter(),
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        match self {
+            Entries::One(entry) => vec![entry],
+            Entries::Many(entries) => entries,
+        }
+    }
+
+    /// The single entry for a reference without a label, or the entry with that label.
+    pub fn find(&self, label: Option<&str>, label_of: fn(&T) -> &String) -> Option<&T> {
+        match (self, label) {
+            (Entries::One(entry), None) => Some(entry),
+            (Entries::Many(entries), Some(label)) => {
+                entries.iter().find(|entry| label_of(entry) == label)
+            }
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn parse_entries<T: DeserializeOwned>(bytes: &[u8]) -> ron::Result<Entries<T>> {
+    if first_token(bytes).map(|i| bytes[i]) == Some(b'[') {
+        ron::de::from_bytes(bytes).map(Entries::Many)
+    } else {
+        ron::de::from_bytes(bytes).map(Entries::One)
+    }
+}
+
+/// Where the first byte that isn't whitespace, a comment or a `#![...]` attribute is.
+pub(crate) fn first_token(bytes: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    loop {
+        let rest = &bytes[i..];
+        let skip = match rest {
+            [] => return None,
+            [b'#', b'!', ..] => rest
+                .iter()
+                .position(|&c| c == b']')
+                .map_or(rest.len(), |end| end + 1),
+            _ => match trivia(rest) {
+                Some(skip) => skip,
+                None => return Some(i),
+            },
+        };
+        i += skip;
+    }
+}
+
+/// How long the whitespace or comment at the start of `bytes` is, if there is one.
+pub(crate) fn trivia(bytes: &[u8]) -> Option<usize> {
+    match bytes {
+        [c, ..] if c.is_ascii_whitespace() => Some(1),
+        [b'/', b'/', ..] => Some(
+            bytes
+                .iter()
+                .position(|&c| c == b'\n')
+                .unwrap_or(bytes.len()),
+        ),
+        [b'/', b'*', ..] => Some(
+            bytes
+                .windows(2)
+                .position(|w| w == b"*/")
+                .map_or(bytes.len(), |end| end + 2),
+        ),
+        _ => None,
+    }
+}
+
+/// A `"file#label"` reference to an asset, or the asset itself, written in place.
+#[derive(Debug, Clone)]
+pub(crate) enum AssetRef<T> {
+    Path(String),
+    Inline(T),
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for AssetRef<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AssetRefVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for AssetRefVisitor<T> {
+            type Value = AssetRef<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a \"file#label\" reference or an inline definition")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(AssetRef::Path(v.to_string()))
+            }
+
+            // Not buffered through an untagged enum, which would lose the enums inside `T`
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                T::deserialize(MapAccessDeserializer::new(map)).map(AssetRef::Inline)
+            }
+        }
+
+        deserializer.deserialize_any(AssetRefVisitor(PhantomData))
+    }
+}
+
+impl<T: Serialize> Serialize for AssetRef<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            AssetRef::Path(path) => serializer.serialize_str(path),
+            AssetRef::Inline(asset) => asset.serialize(serializer),
+        }
+    }
+}
+
+/// Adds `asset` to the file being loaded, labeled `label`, unless another of its assets
+/// already has that label.
+pub(crate) fn set_labeled_asset<T: Asset>(
+    load_context: &mut LoadContext,
+    label: &str,
+    asset: T,
+) -> Result<Handle<T>> {
+    if load_context.has_labeled_asset(label) {
+        bail!("more than one asset is labeled '{}'", label);
+    }
+    Ok(load_context.set_labeled_asset(label, LoadedAsset::new(asset)))
+}
+
+/// The label of an asset defined inline, as the `index`th entry of the asset labeled
+/// `parent` (or of the file's default asset, if `parent` is empty).
+pub(crate) fn inline_label(parent: &str, label: &str, index: usize) -> String {
+    let label = if label.is_empty() {
+        index.to_string()
+    } else {
+        label.to_string()
+    };
+    if parent.is_empty() {
+        label
+    } else {
+        format!("{}/{}", parent, label)
+    }
+}
@@ -0,0 +1,268 @@
+use anyhow::{anyhow, bail, Context, Result};
+use bevy::{math::Vec2, sprite::Rect};
+use image::RgbaImage;
+use std::{collections::HashMap, path::Path};
+
+use crate::texture_atlas::TextureAtlasFile;
+
+pub struct PackOptions {
+    /// Empty pixels around each texture, so they don't bleed into each other.
+    pub padding: u32,
+    /// Repack even if the existing atlas has no names, letting indices shift.
+    pub force: bool,
+}
+
+/// Packs every PNG in `input` into one sheet next to `atlas`, and writes `atlas` to match.
+///
+/// Textures are named after their file. Names already in `atlas` keep their index, even
+/// if their image is gone, and new names are added at the end.
+pub fn pack(input: &Path, atlas: &Path, options: &PackOptions) -> Result<Vec<String>> {
+    let mut warnings = Vec::new();
+
+    let mut names: Vec<String> = Vec::new();
+    if atlas.exists() {
+        let existing = TextureAtlasFile::from_file(atlas)
+            .with_context(|| format!("could not read {}", atlas.display()))?;
+        existing
+            .check()
+            .map_err(|e| anyhow!("{}: {}", atlas.display(), e))?;
+        if existing.names().is_empty() && existing.texture_count() > 0 && !options.force {
+            bail!(
+                "{} has no texture names, so its indices can't be kept; use --force to repack anyway",
+                atlas.display()
+            );
+        }
+        names = existing.names().to_vec();
+    }
+
+    let mut images = HashMap::new();
+    let mut new_names = Vec::new();
+    for entry in
+        std::fs::read_dir(input).with_context(|| format!("could not read {}", input.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("png") {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| anyhow!("invalid file name {}", path.display()))?
+            .to_string();
+        let image = image::open(&path)
+            .with_context(|| format!("could not read {}", path.display()))?
+            .into_rgba8();
+        if !names.contains(&name) {
+            new_names.push(name.clone());
+        }
+        images.insert(name, image);
+    }
+    new_names.sort();
+    names.extend(new_names);
+
+    for name in &names {
+        if !images.contains_key(name) {
+            warnings.push(format!("no image for '{}', keeping its index empty", name));
+        }
+    }
+
+    let sizes: Vec<Option<(u32, u32)>> = names
+        .iter()
+        .map(|name| images.get(name).map(|image| image.dimensions()))
+        .collect();
+    let (width, height, textures) = layout(&sizes, options.padding);
+
+    let mut sheet = RgbaImage::new(width.max(1), height.max(1));
+    for (name, rect) in names.iter().zip(&textures) {
+        if let Some(image) = images.get(name) {
+            image::imageops::replace(&mut sheet, image, rect.min.x as u32, rect.min.y as u32);
+        }
+    }
+
+    let png = atlas.with_extension("png");
+    sheet
+        .save(&png)
+        .with_context(|| format!("could not write {}", png.display()))?;
+
+    // Next to the atlas, which is what `src` is relative to
+    let src = png.file_name().unwrap().into();
+    let size = Vec2::new(sheet.width() as f32, sheet.height() as f32);
+    TextureAtlasFile::new(src, size, textures, names)
+        .save(atlas)
+        .with_context(|| format!("could not write {}", atlas.display()))?;
+
+    Ok(warnings)
+}
+
+/// Places textures of the given sizes `padding` pixels away from each other and from the
+/// edges of the sheet, with an empty rectangle for those that are missing.
+///
+/// Returns the size of the sheet and the rectangle of each texture, in input order.
+fn layout(sizes: &[Option<(u32, u32)>], padding: u32) -> (u32, u32, Vec<Rect>) {
+    let padded: Vec<(u32, u32)> = sizes
+        .iter()
+        .map(|size| size.map_or((0, 0), |(w, h)| (w + 2 * padding, h + 2 * padding)))
+        .collect();
+    let (width, height, positions) = shelf_pack(&padded);
+
+    let textures = sizes
+        .iter()
+        .zip(positions)
+        .map(|(size, (x, y))| match size {
+            Some((w, h)) => {
+                let (x, y) = (x + padding, y + padding);
+                Rect {
+                    min: Vec2::new(x as f32, y as f32),
+                    max: Vec2::new((x + w) as f32, (y + h) as f32),
+                }
+            }
+            None => Rect {
+                min: Vec2::ZERO,
+                max: Vec2::ZERO,
+            },
+        })
+        .collect();
+    (width, height, textures)
+}
+
+/// Places rectangles of the given sizes on rows, tallest first, in a roughly square sheet.
+///
+/// Returns the size of the sheet and the position of each rectangle, in input order.
+fn shelf_pack(sizes: &[(u32, u32)]) -> (u32, u32, Vec<(u32, u32)>) {
+    let area: u32 = sizes.iter().map(|(w, h)| w * h).sum();
+    let widest = sizes.iter().map(|(w, _)| *w).max().unwrap_or(0);
+    let max_width = widest.max((area as f32).sqrt().ceil() as u32);
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i].1));
+
+    let mut positions = vec![(0, 0); sizes.len()];
+    let (mut x, mut y, mut row_height, mut width) = (0, 0, 0, 0);
+    for i in order {
+        let (w, h) = sizes[i];
+        if w == 0 || h == 0 {
+            continue;
+        }
+        if x + w > max_width {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+        positions[i] = (x, y);
+        x += w;
+        width = width.max(x);
+        row_height = row_height.max(h);
+    }
+
+    (width, y + row_height, positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZES: &[(u32, u32)] = &[
+        (10, 20),
+        (30, 5),
+        (7, 7),
+        (40, 40),
+        (1, 1),
+        (16, 16),
+        (5, 30),
+        (64, 3),
+    ];
+
+    fn sizes() -> Vec<Option<(u32, u32)>> {
+        SIZES.iter().copied().map(Some).collect()
+    }
+
+    /// Lays the textures out, checking that each has its size and that, with their
+    /// padding, they are inside the sheet and apart from each other.
+    fn check_layout(sizes: &[Option<(u32, u32)>], padding: u32) -> (u32, u32, Vec<Rect>) {
+        let (width, height, textures) = layout(sizes, padding);
+        assert_eq!(textures.len(), sizes.len());
+
+        let padding = Vec2::splat(padding as f32);
+        let mut placed: Vec<Rect> = Vec::new();
+        for (size, rect) in sizes.iter().zip(&textures) {
+            let (w, h) = match size {
+                Some(size) => *size,
+                None => continue,
+            };
+            assert_eq!(rect.max - rect.min, Vec2::new(w as f32, h as f32));
+
+            let padded = Rect {
+                min: rect.min - padding,
+                max: rect.max + padding,
+            };
+            assert!(
+                padded.min.x >= 0.
+                    && padded.min.y >= 0.
+                    && padded.max.x <= width as f32
+                    && padded.max.y <= height as f32,
+                "{:?} is outside the {}x{} sheet",
+                padded,
+                width,
+                height
+            );
+            for other in &placed {
+                assert!(
+                    padded.max.x <= other.min.x
+                        || other.max.x <= padded.min.x
+                        || padded.max.y <= other.min.y
+                        || other.max.y <= padded.min.y,
+                    "{:?} overlaps {:?}",
+                    padded,
+                    other
+                );
+            }
+            placed.push(padded);
+        }
+        (width, height, textures)
+    }
+
+    #[test]
+    fn no_overlaps() {
+        check_layout(&sizes(), 0);
+    }
+
+    #[test]
+    fn padding() {
+        for padding in [1, 2, 5] {
+            let (_, _, textures) = check_layout(&sizes(), padding);
+            for rect in textures {
+                assert!(rect.min.x >= padding as f32 && rect.min.y >= padding as f32);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_textures() {
+        let mut sizes = sizes();
+        sizes.insert(0, None);
+        sizes.insert(4, None);
+        let (_, _, textures) = check_layout(&sizes, 1);
+        for i in [0, 4] {
+            assert_eq!(textures[i].min, Vec2::ZERO);
+            assert_eq!(textures[i].max, Vec2::ZERO);
+        }
+
+        let (width, height, _) = layout(&[None, None], 1);
+        assert_eq!((width, height), (0, 0));
+    }
+
+    #[test]
+    fn grows_when_full() {
+        let mut area = 0;
+        for n in 1..=40 {
+            let (width, height, _) = check_layout(&vec![Some((16, 16)); n], 1);
+            assert!(width * height >= n as u32 * 18 * 18);
+            assert!(
+                width * height >= area,
+                "the sheet shrank with {} textures",
+                n
+            );
+            area = width * height;
+        }
+    }
+}
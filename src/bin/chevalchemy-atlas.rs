@@ -0,0 +1,47 @@
+use chevalchemy::atlas_packer::{pack, PackOptions};
+use std::path::PathBuf;
+
+// Usage: chevalchemy-atlas [--padding N] [--force] INPUT_DIR OUTPUT.atlas
+//
+// Packs the PNGs in INPUT_DIR into OUTPUT.png, and writes OUTPUT.atlas to match.
+fn main() {
+    let mut options = PackOptions {
+        padding: 1,
+        force: false,
+    };
+    let mut paths = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--padding" => {
+                options.padding = args
+                    .next()
+                    .and_then(|padding| padding.parse().ok())
+                    .expect("missing or invalid value for --padding");
+            }
+            "--force" => options.force = true,
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let (input, atlas) = match paths.as_slice() {
+        [input, atlas] => (input, atlas),
+        _ => {
+            eprintln!("usage: chevalchemy-atlas [--padding N] [--force] INPUT_DIR OUTPUT.atlas");
+            std::process::exit(2);
+        }
+    };
+
+    match pack(input, atlas, &options) {
+        Ok(warnings) => {
+            for warning in warnings {
+                eprintln!("warning: {}", warning);
+            }
+            println!("wrote {}", atlas.display());
+        }
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
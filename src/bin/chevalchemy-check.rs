@@ -0,0 +1,28 @@
+use chevalchemy::check::check_assets;
+
+// Usage: chevalchemy-check [--assets DIR]
+//
+// Checks that every item, bundle, level and texture atlas file parses, and that they
+// all fit together.
+fn main() {
+    let mut assets = "assets".to_string();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--assets" {
+            assets = args.next().expect("missing value for --assets");
+        } else {
+            eprintln!("error: unexpected argument '{}'", arg);
+            std::process::exit(2);
+        }
+    }
+
+    let problems = check_assets(&assets);
+    for problem in &problems {
+        println!("{}", problem);
+    }
+
+    if !problems.is_empty() {
+        eprintln!("{} problem(s) found", problems.len());
+        std::process::exit(1);
+    }
+}
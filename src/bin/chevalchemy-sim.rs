@@ -0,0 +1,76 @@
+use chevalchemy::{
+    replay::Replay,
+    simulation::{check_stability, load_levels, play_replay, DEFAULT_MAX_TIME},
+};
+
+// Usage: chevalchemy-sim [--assets DIR] [REPLAY...]
+//
+// Without replays, checks that every level settles on its own. With replays, checks
+// that each of them completes its level.
+fn main() {
+    let mut assets = "assets".to_string();
+    let mut replays = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--assets" {
+            assets = args.next().expect("missing value for --assets");
+        } else {
+            replays.push(arg);
+        }
+    }
+
+    let levels = match load_levels(&assets, "levels.levels") {
+        Ok(levels) => levels,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let mut ok = true;
+    if replays.is_empty() {
+        for level in &levels {
+            match check_stability(level, DEFAULT_MAX_TIME) {
+                Ok(report) => {
+                    println!("{}", report);
+                    ok &= report.is_stable();
+                }
+                Err(e) => {
+                    eprintln!("error: {:#}", e);
+                    ok = false;
+                }
+            }
+        }
+    } else {
+        for path in &replays {
+            let replay = match Replay::from_file(path) {
+                Ok(replay) => replay,
+                Err(e) => {
+                    eprintln!("error: could not load replay '{}': {:#}", path, e);
+                    ok = false;
+                    continue;
+                }
+            };
+            match levels.iter().find(|level| level.label == replay.level) {
+                Some(level) => match play_replay(level, &replay) {
+                    Ok(report) => {
+                        println!("{}: {}", path, report);
+                        ok &= report.is_completed();
+                    }
+                    Err(e) => {
+                        eprintln!("error: {}: {:#}", path, e);
+                        ok = false;
+                    }
+                },
+                None => {
+                    eprintln!("error: {}: unknown level '{}'", path, replay.level);
+                    ok = false;
+                }
+            }
+        }
+    }
+
+    if !ok {
+        std::process::exit(1);
+    }
+}
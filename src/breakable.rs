@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+use bevy_rapier2d::{na, prelude::*};
+
+use crate::{items::Item, IsItem, ItemBrokenEvent};
+
+/// Makes an item shatter into `fragments` when something pushes on it too hard.
+#[derive(Debug, Clone)]
+pub struct Breakable {
+    /// The contact force above which the item breaks.
+    pub threshold: f32,
+    /// The items it breaks into, relative to its own position and rotation. Untyped,
+    /// since a typed handle to an item can't be part of an item.
+    pub fragments: Vec<(Vec2, HandleUntyped)>,
+}
+
+/// Replaces items hit harder than their threshold by their fragments.
+pub fn break_items(
+    mut commands: Commands,
+    mut item_broken_events: EventWriter<ItemBrokenEvent>,
+    narrow_phase: Res<NarrowPhase>,
+    integration_parameters: Res<IntegrationParameters>,
+    items: Res<Assets<Item>>,
+    breakables: Query<(
+        Entity,
+        &Breakable,
+        &IsItem,
+        &RigidBodyPosition,
+        &RigidBodyVelocity,
+        Option<&Children>,
+    )>,
+) {
+    for (entity, breakable, is_item, position, velocity, children) in breakables.iter() {
+        // Colliders with their own material are children of the item
+        let colliders =
+            std::iter::once(entity).chain(children.into_iter().flat_map(|c| c.iter().copied()));
+        let force = colliders
+            .map(|collider| contact_impulse(&narrow_phase, collider))
+            .fold(0., f32::max)
+            / integration_parameters.dt;
+        if force <= breakable.threshold {
+            continue;
+        }
+
+        for (offset, fragment) in &breakable.fragments {
+            let fragment = match items.get(fragment) {
+                Some(fragment) => fragment,
+                None => {
+                    eprintln!("error: could not find item '{:?}'", fragment);
+                    continue;
+                }
+            };
+            let at = position.position * na::Point2::new(offset.x, offset.y);
+            let mut fragment_commands = commands.spawn();
+            fragment.spawn(&mut fragment_commands, Vec2::new(at.x, at.y));
+            fragment_commands
+                .insert(RigidBodyPosition::from(na::Isometry2::from_parts(
+                    at.coords.into(),
+                    position.position.rotation,
+                )))
+                .insert(velocity.clone());
+        }
+
+        commands.entity(entity).despawn_recursive();
+        item_broken_events.send(ItemBrokenEvent(is_item.0.clone()));
+    }
+}
+
+/// The largest impulse applied to the collider of `entity` by any one contact manifold
+/// during the last step.
+fn contact_impulse(narrow_phase: &NarrowPhase, entity: Entity) -> f32 {
+    narrow_phase
+        .contacts_with(entity.handle())
+        .flat_map(|pair| pair.manifolds.iter())
+        .map(|manifold| manifold.points.iter().map(|point| point.data.impulse).sum())
+        .fold(0., f32::max)
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use bevy::{asset::LoadContext, prelude::*};
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    items::{check_shapes, collider_shape, Shape},
+    reactions::Mixture,
+    recipe::{CurrentRecipe, RecipeStep},
+    sprite::{sprite_asset, SpriteFile, SpriteHandle},
+    LevelEntity,
+};
+
+/// A cauldron in a level file. Anything left out is taken from the default cauldron.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub(crate) struct CauldronFile {
+    pub position: Vec2,
+    pub sprite: SpriteFile,
+    /// The walls, relative to `position`.
+    pub walls: Vec<Shape>,
+    /// Where items count as being in the cauldron, relative to `position`.
+    pub sensor: Shape,
+    /// Its own recipe, instead of the level's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipe: Option<Vec<RecipeStep>>,
+}
+
+impl Default for CauldronFile {
+    fn default() -> Self {
+        let polygon = |vertices: &[[f32; 2]]| {
+            Shape::ConvexPolygon(vertices.iter().map(|&v| Vec2::from(v)).collect())
+        };
+        Self {
+            position: Vec2::new(1., -150. + 61.),
+            sprite: SpriteFile::Image("cauldron.png".to_string()),
+            walls: vec![
+                polygon(&[[-53.5, 40.0], [-49.5, 38.0], [-58.5, 0.0], [-60.5, 8.0]]),
+                polygon(&[[-60.5, 7.5], [-42.0, -15.0], [-54.5, -9.5], [-60.0, -1.0]]),
+                polygon(&[[-46.0, -12.5], [48.5, -12.5], [26.0, -18.5], [-22.0, -18.0]]),
+                polygon(&[[48.5, -14.0], [55.5, -9.0], [62.0, 3.0], [60.5, 16.0]]),
+                polygon(&[[61.0, 12.0], [52.0, 40.0], [51.0, 35.0], [60.0, 3.0]]),
+            ],
+            sensor: polygon(&[[-55.5, 4.5], [58.5, 5.5], [45.5, -11.5], [-45.0, -10.5]]),
+            recipe: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cauldron {
+    pub(crate) file: CauldronFile,
+    sprite: SpriteHandle,
+}
+
+/// The sensor of the `index`th cauldron of the level. It holds the cauldron's recipe
+/// and mixture.
+pub struct CauldronSensor {
+    pub index: usize,
+    pub position: Vec2,
+}
+
+/// Builds a cauldron, adding the material for its sprite as an asset labeled `label`.
+pub(crate) fn cauldron_asset(
+    cauldron: CauldronFile,
+    label: &str,
+    load_context: &mut LoadContext,
+) -> Result<Cauldron> {
+    check_shapes(
+        cauldron
+            .walls
+            .iter()
+            .chain(std::iter::once(&cauldron.sensor)),
+        label,
+    )?;
+    let sprite = sprite_asset(&cauldron.sprite, label, load_context)?;
+    Ok(Cauldron {
+        file: cauldron,
+        sprite,
+    })
+}
+
+impl Cauldron {
+    pub fn spawn(&self, commands: &mut Commands, index: usize, recipe: &[RecipeStep]) {
+        let CauldronFile {
+            position,
+            walls,
+            sensor,
+            ..
+        } = &self.file;
+        let mut entity = commands.spawn();
+        // In front of the items
+        self.sprite
+            .insert(&mut entity, Transform::from_xyz(0., 0., 100.));
+
+        let walls: Vec<_> = walls
+            .iter()
+            .filter_map(|wall| Some((Vec2::ZERO.into(), collider_shape(wall)?)))
+            .collect();
+        entity
+            .insert_bundle(RigidBodyBundle {
+                body_type: RigidBodyType::Static,
+                position: (*position).into(),
+                ..Default::default()
+            })
+            .insert(RigidBodyPositionSync::Discrete)
+            .insert(LevelEntity);
+        if !walls.is_empty() {
+            entity.insert_bundle(ColliderBundle {
+                shape: ColliderShape::compound(walls),
+                ..Default::default()
+            });
+        }
+        if let Some(sensor) = collider_shape(sensor) {
+            entity.with_children(|parent| {
+                parent
+                    .spawn_bundle(ColliderBundle {
+                        collider_type: ColliderType::Sensor,
+                        shape: sensor,
+                        ..Default::default()
+                    })
+                    .insert(CauldronSensor {
+                        index,
+                        position: *position,
+                    })
+                    .insert(CurrentRecipe::new(recipe))
+                    .insert(Mixture::default());
+            });
+        }
+    }
+}
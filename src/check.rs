@@ -0,0 +1,638 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::Path,
+};
+
+use crate::{
+    asset_files::{inline_label, parse_entries, split_reference, AssetRef, Entries},
+    cauldron::CauldronFile,
+    items::{Collider, ItemBundleFile, ItemFile},
+    levels::LevelFile,
+    motion::Motion,
+    reactions::{EffectFile, ReactionFile},
+    scenery::SceneryFile,
+    sprite::SpriteFile,
+    texture_atlas::TextureAtlasFile,
+    tools::{ToolFile, TOOLS_FILE},
+};
+
+/// Something wrong with an asset file.
+pub struct Problem {
+    pub file: String,
+    pub line: Option<usize>,
+    pub label: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if let Some(label) = &self.label {
+            write!(f, ": {}", label)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+struct Parsed<T> {
+    text: String,
+    contents: T,
+}
+
+impl<T> Parsed<T> {
+    /// The line where the entry with this label starts, if it can be found.
+    fn line_of(&self, label: &str) -> Option<usize> {
+        let needle = format!("label: \"{}\"", label);
+        self.text
+            .lines()
+            .position(|line| line.contains(&needle))
+            .map(|index| index + 1)
+    }
+}
+
+#[derive(Default)]
+struct Files {
+    items: BTreeMap<String, Parsed<Entries<ItemFile>>>,
+    bundles: BTreeMap<String, Parsed<Entries<ItemBundleFile>>>,
+    levels: BTreeMap<String, Parsed<Entries<LevelFile>>>,
+    reactions: BTreeMap<String, Parsed<Entries<ReactionFile>>>,
+    scenery: BTreeMap<String, Parsed<Entries<SceneryFile>>>,
+    tools: BTreeMap<String, Parsed<Entries<ToolFile>>>,
+    atlases: BTreeMap<String, Parsed<TextureAtlasFile>>,
+}
+
+/// Parses every asset file under `root` and checks that they fit together.
+pub fn check_assets(root: impl AsRef<Path>) -> Vec<Problem> {
+    let root = root.as_ref();
+    let mut problems = Vec::new();
+    let mut files = Files::default();
+
+    let mut paths = Vec::new();
+    if let Err(e) = find_files(root, root, &mut paths) {
+        problems.push(Problem {
+            file: root.display().to_string(),
+            line: None,
+            label: None,
+            message: e.to_string(),
+        });
+    }
+    paths.sort();
+
+    for path in paths {
+        let extension = Path::new(&path).extension().and_then(|ext| ext.to_str());
+        if !matches!(
+            extension,
+            Some("items" | "bundles" | "levels" | "reactions" | "scenery" | "tools" | "atlas")
+        ) {
+            continue;
+        }
+
+        let text = match std::fs::read_to_string(root.join(&path)) {
+            Ok(text) => text,
+            Err(e) => {
+                problems.push(Problem {
+                    file: path,
+                    line: None,
+                    label: None,
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        match extension {
+            Some("items") => parse(path, text, parse_entries, &mut files.items, &mut problems),
+            Some("bundles") => parse(path, text, parse_entries, &mut files.bundles, &mut problems),
+            Some("levels") => parse(path, text, parse_entries, &mut files.levels, &mut problems),
+            Some("reactions") => parse(
+                path,
+                text,
+                parse_entries,
+                &mut files.reactions,
+                &mut problems,
+            ),
+            Some("scenery") => parse(path, text, parse_entries, &mut files.scenery, &mut problems),
+            Some("tools") => parse(path, text, parse_entries, &mut files.tools, &mut problems),
+            _ => parse(
+                path,
+                text,
+                |bytes| ron::de::from_bytes(bytes),
+                &mut files.atlases,
+                &mut problems,
+            ),
+        }
+    }
+
+    check_atlases(root, &files, &mut problems);
+    check_items(&files, &mut problems);
+    check_bundles(&files, &mut problems);
+    check_sceneries(root, &files, &mut problems);
+    check_tools(root, &files, &mut problems);
+    check_levels(root, &files, &mut problems);
+    check_reactions(&files, &mut problems);
+    problems
+}
+
+/// Collects the paths of all files under `dir`, relative to `root` and with `/` separators.
+fn find_files(root: &Path, dir: &Path, paths: &mut Vec<String>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_files(root, &path, paths)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            let components: Vec<_> = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect();
+            paths.push(components.join("/"));
+        }
+    }
+    Ok(())
+}
+
+fn parse<T>(
+    path: String,
+    text: String,
+    parse: fn(&[u8]) -> ron::Result<T>,
+    files: &mut BTreeMap<String, Parsed<T>>,
+    problems: &mut Vec<Problem>,
+) {
+    match parse(text.as_bytes()) {
+        Ok(contents) => {
+            files.insert(path, Parsed { text, contents });
+        }
+        Err(e) => problems.push(Problem {
+            file: path,
+            line: Some(e.position.line),
+            label: None,
+            message: e.code.to_string(),
+        }),
+    }
+}
+
+fn resolve<'a, T>(
+    files: &'a BTreeMap<String, Parsed<Entries<T>>>,
+    reference: &'a AssetRef<T>,
+    label_of: fn(&T) -> &String,
+) -> Result<&'a T, String> {
+    let reference = match reference {
+        AssetRef::Path(reference) => reference,
+        AssetRef::Inline(asset) => return Ok(asset),
+    };
+    let (file, label) = split_reference(reference);
+    let entries = &files
+        .get(file)
+        .ok_or_else(|| format!("could not find file '{}' for '{}'", file, reference))?
+        .contents;
+    match (entries, label) {
+        (Entries::One(_), Some(_)) => Err(format!(
+            "'{}' holds a single entry, refer to it without a label",
+            file
+        )),
+        (Entries::Many(_), None) => Err(format!("missing label in reference '{}'", reference)),
+        _ => entries
+            .find(label, label_of)
+            .ok_or_else(|| format!("could not find '{}'", reference)),
+    }
+}
+
+/// Where to report problems with an entry, which may be defined inline in another file.
+struct Location<'a> {
+    file: &'a str,
+    line: Option<usize>,
+    label: String,
+}
+
+impl Location<'_> {
+    fn report(&self, problems: &mut Vec<Problem>, message: String) {
+        problems.push(Problem {
+            file: self.file.to_string(),
+            line: self.line,
+            label: Some(self.label.clone()),
+            message,
+        });
+    }
+
+    fn inline(&self, label: &str, index: usize) -> Self {
+        Location {
+            file: self.file,
+            line: self.line,
+            label: inline_label(&self.label, label, index),
+        }
+    }
+}
+
+fn location<'a, T>(file: &'a str, parsed: &Parsed<T>, label: &str) -> Location<'a> {
+    Location {
+        file,
+        line: parsed.line_of(label),
+        label: label.to_string(),
+    }
+}
+
+fn check_atlases(root: &Path, files: &Files, problems: &mut Vec<Problem>) {
+    for (file, atlas) in &files.atlases {
+        if !atlas.contents.src_next_to(&root.join(file)).exists() {
+            problems.push(Problem {
+                file: file.clone(),
+                line: None,
+                label: None,
+                message: format!("could not find image '{}'", atlas.contents.src().display()),
+            });
+        }
+        if let Err(message) = atlas.contents.check() {
+            problems.push(Problem {
+                file: file.clone(),
+                line: None,
+                label: None,
+                message,
+            });
+        }
+    }
+}
+
+fn check_items(files: &Files, problems: &mut Vec<Problem>) {
+    for (file, items) in &files.items {
+        for item in items.contents.iter() {
+            check_item(files, &location(file, items, &item.label), item, problems);
+        }
+    }
+}
+
+fn check_item(files: &Files, location: &Location, item: &ItemFile, problems: &mut Vec<Problem>) {
+    match files.atlases.get(&item.texture_atlas) {
+        Some(atlas) => {
+            let count = atlas.contents.texture_count();
+            if item.texture_index as usize >= count {
+                location.report(
+                    problems,
+                    format!(
+                        "texture_index {} is out of range, '{}' has {} textures",
+                        item.texture_index, item.texture_atlas, count
+                    ),
+                );
+            }
+        }
+        None => location.report(
+            problems,
+            format!("could not find texture atlas '{}'", item.texture_atlas),
+        ),
+    }
+
+    if let Err(e) = item.material.check() {
+        location.report(problems, format!("material: {}", e));
+    }
+
+    check_colliders(location, &item.colliders, problems);
+
+    if let Some(breakable) = &item.breakable {
+        if breakable.threshold <= 0. {
+            location.report(
+                problems,
+                format!("break threshold {} is not positive", breakable.threshold),
+            );
+        }
+        for (i, (_, fragment)) in breakable.fragments.iter().enumerate() {
+            check_item_ref(files, location, i, fragment, problems);
+        }
+    }
+}
+
+fn check_colliders(location: &Location, colliders: &[Collider], problems: &mut Vec<Problem>) {
+    for (i, collider) in colliders.iter().enumerate() {
+        if let Err(e) = collider.shape.check() {
+            location.report(problems, format!("collider {}: {}", i, e));
+        }
+        if let Some(material) = &collider.material {
+            if let Err(e) = material.check() {
+                location.report(problems, format!("collider {} material: {}", i, e));
+            }
+            if material.has_body_settings() {
+                location.report(
+                    problems,
+                    format!(
+                        "collider {} material: damping and can_sleep only apply to whole items",
+                        i
+                    ),
+                );
+            }
+        }
+    }
+}
+
+fn check_bundles(files: &Files, problems: &mut Vec<Problem>) {
+    for (file, bundles) in &files.bundles {
+        for bundle in bundles.contents.iter() {
+            check_bundle(
+                files,
+                &location(file, bundles, &bundle.label),
+                bundle,
+                problems,
+            );
+        }
+    }
+}
+
+fn check_bundle(
+    files: &Files,
+    location: &Location,
+    bundle: &ItemBundleFile,
+    problems: &mut Vec<Problem>,
+) {
+    for (i, (_, item)) in bundle.items.iter().enumerate() {
+        check_item_ref(files, location, i, item, problems);
+    }
+    for (i, joint) in bundle.joints.iter().enumerate() {
+        if let Err(e) = joint.check(bundle.items.len()) {
+            location.report(problems, format!("joint {}: {}", i, e));
+        }
+    }
+}
+
+/// Checks that a referenced item exists, or that an inline one is valid.
+fn check_item_ref(
+    files: &Files,
+    location: &Location,
+    index: usize,
+    item: &AssetRef<ItemFile>,
+    problems: &mut Vec<Problem>,
+) {
+    match item {
+        AssetRef::Path(_) => {
+            if let Err(message) = resolve(&files.items, item, |item| &item.label) {
+                location.report(problems, message);
+            }
+        }
+        AssetRef::Inline(item) => {
+            check_item(files, &location.inline(&item.label, index), item, problems)
+        }
+    }
+}
+
+fn check_sceneries(root: &Path, files: &Files, problems: &mut Vec<Problem>) {
+    for (file, sceneries) in &files.scenery {
+        for scenery in sceneries.contents.iter() {
+            check_scenery(
+                root,
+                files,
+                &location(file, sceneries, &scenery.label),
+                scenery,
+                problems,
+            );
+        }
+    }
+}
+
+fn check_scenery(
+    root: &Path,
+    files: &Files,
+    location: &Location,
+    scenery: &SceneryFile,
+    problems: &mut Vec<Problem>,
+) {
+    for (i, piece) in scenery.pieces.iter().enumerate() {
+        let location = location.inline(&format!("piece{}", i), i);
+        if let Err(e) = piece.material.check() {
+            location.report(problems, format!("material: {}", e));
+        }
+        if piece.material.has_body_settings() {
+            location.report(
+                problems,
+                "material: damping and can_sleep don't apply to scenery".to_string(),
+            );
+        }
+        check_colliders(&location, &piece.colliders, problems);
+        if let Some(Err(e)) = piece.motion.as_ref().map(Motion::check) {
+            location.report(problems, format!("motion: {}", e));
+        }
+        if let Some(sprite) = &piece.sprite {
+            check_sprite(root, files, &location, sprite, problems);
+        }
+    }
+}
+
+fn check_tools(root: &Path, files: &Files, problems: &mut Vec<Problem>) {
+    for (file, tools) in &files.tools {
+        for tool in tools.contents.iter() {
+            let location = location(file, tools, &tool.label);
+            if let Err(e) = tool.material.check() {
+                location.report(problems, format!("material: {}", e));
+            }
+            if tool.material.has_body_settings() {
+                location.report(
+                    problems,
+                    "material: damping and can_sleep don't apply to tools".to_string(),
+                );
+            }
+            check_colliders(&location, &tool.colliders, problems);
+            if let Some(sprite) = &tool.sprite {
+                check_sprite(root, files, &location, sprite, problems);
+            }
+            if let Err(e) = tool.check_settings() {
+                location.report(problems, e);
+            }
+        }
+    }
+}
+
+fn check_levels(root: &Path, files: &Files, problems: &mut Vec<Problem>) {
+    for (file, levels) in &files.levels {
+        for level in levels.contents.iter() {
+            let location = location(file, levels, &level.label);
+
+            // How many items of each type the level starts with
+            let mut types: HashMap<&str, usize> = HashMap::new();
+            for (i, (_, bundle)) in level.bundles.iter().enumerate() {
+                if let AssetRef::Inline(bundle) = bundle {
+                    check_bundle(files, &location.inline(&bundle.label, i), bundle, problems);
+                }
+                match resolve(&files.bundles, bundle, |bundle| &bundle.label) {
+                    Ok(bundle) => {
+                        // Broken item references are reported with the bundle
+                        let items = bundle.items.iter().filter_map(|(_, item)| {
+                            resolve(&files.items, item, |item| &item.label).ok()
+                        });
+                        for item in items {
+                            *types.entry(&item.ty).or_default() += 1;
+                        }
+                    }
+                    Err(message) => location.report(problems, message),
+                }
+            }
+
+            for (i, (_, scenery)) in level.scenery.iter().enumerate() {
+                match scenery {
+                    AssetRef::Inline(scenery) => {
+                        let label = match scenery.label.as_str() {
+                            "" => format!("scenery{}", i),
+                            label => label.to_string(),
+                        };
+                        let location = location.inline(&label, i);
+                        check_scenery(root, files, &location, scenery, problems);
+                    }
+                    AssetRef::Path(_) => {
+                        if let Err(message) =
+                            resolve(&files.scenery, scenery, |scenery| &scenery.label)
+                        {
+                            location.report(problems, message);
+                        }
+                    }
+                }
+            }
+
+            for label in &level.tools {
+                let known = files.tools.get(TOOLS_FILE).map_or(false, |tools| {
+                    tools.contents.iter().any(|tool| tool.label == *label)
+                });
+                if !known {
+                    location.report(
+                        problems,
+                        format!("could not find tool '{}' in '{}'", label, TOOLS_FILE),
+                    );
+                }
+            }
+
+            for (i, cauldron) in level.cauldrons.iter().enumerate() {
+                let location = location.inline(&format!("cauldron{}", i), i);
+                check_cauldron(root, files, &location, cauldron, problems);
+            }
+            let recipes: Vec<_> = if level.cauldrons.is_empty() {
+                vec![&level.recipe]
+            } else {
+                level
+                    .cauldrons
+                    .iter()
+                    .map(|cauldron| cauldron.recipe.as_ref().unwrap_or(&level.recipe))
+                    .collect()
+            };
+
+            // Ingredients that accept the same types draw from the same items, even
+            // across cauldrons
+            let mut needed: Vec<(&Vec<String>, usize)> = Vec::new();
+            let ingredients = recipes
+                .into_iter()
+                .flatten()
+                .flat_map(|step| step.ingredients());
+            for ingredient in ingredients {
+                match needed
+                    .iter_mut()
+                    .find(|(types, _)| **types == ingredient.types)
+                {
+                    Some((_, count)) => *count += ingredient.count,
+                    None => needed.push((&ingredient.types, ingredient.count)),
+                }
+            }
+
+            for (needed_types, count) in needed {
+                let available: usize = needed_types
+                    .iter()
+                    .map(|ty| types.get(ty.as_str()).copied().unwrap_or_default())
+                    .sum();
+                if available < count {
+                    location.report(
+                        problems,
+                        format!(
+                            "recipe needs {} '{}' but the level only provides {}",
+                            count,
+                            needed_types.join("|"),
+                            available
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn check_cauldron(
+    root: &Path,
+    files: &Files,
+    location: &Location,
+    cauldron: &CauldronFile,
+    problems: &mut Vec<Problem>,
+) {
+    for (i, wall) in cauldron.walls.iter().enumerate() {
+        if let Err(e) = wall.check() {
+            location.report(problems, format!("wall {}: {}", i, e));
+        }
+    }
+    if let Err(e) = cauldron.sensor.check() {
+        location.report(problems, format!("sensor: {}", e));
+    }
+    check_sprite(root, files, location, &cauldron.sprite, problems);
+}
+
+fn check_sprite(
+    root: &Path,
+    files: &Files,
+    location: &Location,
+    sprite: &SpriteFile,
+    problems: &mut Vec<Problem>,
+) {
+    match sprite {
+        SpriteFile::Image(path) => {
+            if !root.join(path).exists() {
+                location.report(problems, format!("could not find image '{}'", path));
+            }
+        }
+        SpriteFile::Atlas(path, texture_index) => match files.atlases.get(path) {
+            Some(atlas) => {
+                let count = atlas.contents.texture_count();
+                if *texture_index as usize >= count {
+                    location.report(
+                        problems,
+                        format!(
+                            "texture index {} is out of range, '{}' has {} textures",
+                            texture_index, path, count
+                        ),
+                    );
+                }
+            }
+            None => location.report(problems, format!("could not find texture atlas '{}'", path)),
+        },
+        SpriteFile::Rect((r, g, b), size) => {
+            if [r, g, b].iter().any(|c| !(0. ..=1.).contains(*c)) {
+                location.report(
+                    problems,
+                    format!("color ({}, {}, {}) is out of range", r, g, b),
+                );
+            }
+            if size.x <= 0. || size.y <= 0. {
+                location.report(problems, format!("size {} is not positive", size));
+            }
+        }
+    }
+}
+
+fn check_reactions(files: &Files, problems: &mut Vec<Problem>) {
+    for (file, reactions) in &files.reactions {
+        for reaction in reactions.contents.iter() {
+            let location = location(file, reactions, &reaction.label);
+
+            if reaction.sequence.is_empty() {
+                location.report(problems, "sequence is empty".to_string());
+            }
+
+            for (i, effect) in reaction.effects.iter().enumerate() {
+                match effect {
+                    EffectFile::Color(r, g, b) => {
+                        if [r, g, b].iter().any(|c| !(0. ..=1.).contains(*c)) {
+                            location.report(
+                                problems,
+                                format!("color ({}, {}, {}) is out of range", r, g, b),
+                            );
+                        }
+                    }
+                    EffectFile::Spawn(item, _) => {
+                        check_item_ref(files, &location, i, item, problems)
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
@@ -1,19 +1,30 @@
 use bevy::prelude::*;
-use bevy_egui::{egui, EguiContext, EguiPlugin};
+use bevy_egui::{egui, EguiContext};
 use std::collections::HashMap;
 
 use crate::{
-    items::{Item, ItemBundle, SpawnItemBundleExt},
+    asset_files::AssetRef,
+    cauldron::CauldronFile,
+    handles::Handles,
+    items::{Item, ItemBundle, ItemBundleFile, SpawnItemBundleExt},
+    levels::{self, save_level, Level, LevelFile, Par},
     mouse_position_world::MousePositionWorld,
+    scenery::{Scenery, SceneryFile},
+    CurrentLevel, IsItem,
 };
 
+mod colliders;
+
 pub struct EditorPlugin;
 
 impl Plugin for EditorPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_plugin(EguiPlugin)
-            .insert_resource(EguiTextures::default())
-            .add_system(ui_bundle_selector.system());
+        app.insert_resource(EguiTextures::default())
+            .insert_resource(EditorScene::default())
+            .init_resource::<colliders::ColliderEditor>()
+            .add_system(ui_bundle_selector.system())
+            .add_system(ui_scene.system())
+            .add_system(colliders::ui_colliders.system());
     }
 }
 
@@ -41,6 +52,106 @@ impl EguiTextures {
     }
 }
 
+struct EditorScene {
+    path: String,
+    label: String,
+    recipe: String,
+    cauldrons: Vec<CauldronFile>,
+    scenery: Vec<(Vec2, Handle<Scenery>)>,
+    bundles: Vec<(Vec2, Handle<ItemBundle>)>,
+    tools: Vec<String>,
+    par: Par,
+    status: String,
+}
+
+impl Default for EditorScene {
+    fn default() -> Self {
+        Self {
+            path: "assets/levels.levels".to_string(),
+            label: String::new(),
+            recipe: "[]".to_string(),
+            cauldrons: Vec::new(),
+            scenery: Vec::new(),
+            bundles: Vec::new(),
+            tools: Vec::new(),
+            par: Par::default(),
+            status: String::new(),
+        }
+    }
+}
+
+impl EditorScene {
+    fn to_level_file(
+        &self,
+        asset_server: &AssetServer,
+        sceneries: &Assets<Scenery>,
+        bundles: &Assets<ItemBundle>,
+    ) -> Result<LevelFile, String> {
+        let scenery: Vec<(Vec2, AssetRef<SceneryFile>)> = self
+            .scenery
+            .iter()
+            .map(|(position, scenery)| {
+                let scenery = asset_ref(asset_server, sceneries, scenery, |s| &s.file)?;
+                Ok((*position, scenery))
+            })
+            .collect::<Result<_, String>>()?;
+        let bundles: Vec<(Vec2, AssetRef<ItemBundleFile>)> = self
+            .bundles
+            .iter()
+            .map(|(position, bundle)| {
+                let bundle = asset_ref(asset_server, bundles, bundle, |b| &b.file)?;
+                Ok((*position, bundle))
+            })
+            .collect::<Result<_, String>>()?;
+
+        // The default cauldron is left out, as in hand-written levels
+        let cauldrons = if self.cauldrons == [CauldronFile::default()] {
+            Vec::new()
+        } else {
+            self.cauldrons.clone()
+        };
+
+        Ok(LevelFile {
+            label: self.label.trim().to_string(),
+            recipe: ron::de::from_str(&self.recipe)
+                .map_err(|e| format!("invalid recipe: {}", e))?,
+            cauldrons,
+            scenery,
+            bundles,
+            tools: self.tools.clone(),
+            par: self.par.clone(),
+        })
+    }
+}
+
+/// How a level refers to the asset behind `handle`: written in place if it was defined
+/// inline in a level, or else as the file, and label if any, that it was loaded from.
+fn asset_ref<T: bevy::asset::Asset, F: Clone>(
+    asset_server: &AssetServer,
+    assets: &Assets<T>,
+    handle: &Handle<T>,
+    file_of: fn(&T) -> &F,
+) -> Result<AssetRef<F>, String> {
+    let path = asset_server
+        .get_handle_path(handle)
+        .ok_or_else(|| format!("unknown path for '{:?}'", handle))?;
+    let is_level = path.path().extension().map_or(false, |ext| {
+        levels::FILE_EXTENSIONS.iter().any(|e| ext == *e)
+    });
+    if is_level {
+        let asset = assets
+            .get(handle)
+            .ok_or_else(|| format!("'{:?}' is not loaded", path))?;
+        return Ok(AssetRef::Inline(file_of(asset).clone()));
+    }
+
+    let path = match path.label() {
+        Some(label) => format!("{}#{}", path.path().display(), label),
+        None => path.path().display().to_string(),
+    };
+    Ok(AssetRef::Path(path))
+}
+
 fn show_bundle(
     ui: &mut egui::Ui,
     egui_textures: &mut ResMut<EguiTextures>,
@@ -52,14 +163,31 @@ fn show_bundle(
     let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
     let center = rect.center();
 
+    // Items that haven't loaded, or whose texture is out of range, are left out, and the
+    // bundle greyed out
+    let sprites: Vec<_> = bundle
+        .items
+        .iter()
+        .filter_map(|(offset, item)| {
+            let item = items.get(item)?;
+            let texture_atlas = texture_atlases.get(&item.texture_atlas)?;
+            let tex_rect = texture_atlas.textures.get(item.texture_index as usize)?;
+            Some((*offset, texture_atlas, tex_rect))
+        })
+        .collect();
+    if sprites.len() < bundle.items.len() {
+        ui.painter()
+            .rect_filled(rect, 0., egui::Color32::from_gray(64));
+    }
+    if sprites.is_empty() {
+        return response;
+    }
+
     let bounds = {
         let mut min = Vec2::splat(f32::INFINITY);
         let mut max = Vec2::splat(-f32::INFINITY);
-        for (offset, item) in &bundle.items {
+        for (offset, _, tex_rect) in &sprites {
             let offset = *offset * Vec2::new(1.0, -1.0);
-            let item = items.get(item).unwrap();
-            let texture_atlas = texture_atlases.get(&item.texture_atlas).unwrap();
-            let tex_rect = &texture_atlas.textures[item.texture_index as usize];
             let half_size = Vec2::new(tex_rect.width(), tex_rect.height()) / 2.;
 
             min = min.min(offset - half_size);
@@ -73,13 +201,9 @@ fn show_bundle(
 
     let center = center - bounds.center().to_vec2();
 
-    for (offset, item) in &bundle.items {
+    for (offset, texture_atlas, tex_rect) in sprites {
         let image_center = center + egui::Vec2::new(offset.x, -offset.y);
 
-        let item = items.get(item).unwrap();
-        let texture_atlas = texture_atlases.get(&item.texture_atlas).unwrap();
-
-        let tex_rect = &texture_atlas.textures[item.texture_index as usize];
         let tex_uv = egui::Rect {
             min: <[f32; 2]>::from(tex_rect.min / texture_atlas.size).into(),
             max: <[f32; 2]>::from(tex_rect.max / texture_atlas.size).into(),
@@ -108,6 +232,7 @@ fn ui_bundle_selector(
     texture_atlases: Res<Assets<TextureAtlas>>,
     mut egui_textures: ResMut<EguiTextures>,
     mut commands: Commands,
+    mut scene: ResMut<EditorScene>,
     mouse_position_world: Res<MousePositionWorld>,
 ) {
     egui_textures.register_new_textures(&mut egui_context);
@@ -136,9 +261,75 @@ fn ui_bundle_selector(
                     }
                     if response.drag_released() && !ctx.is_pointer_over_area() {
                         commands.spawn_item_bundle(Handle::weak(id), mouse_position_world.0);
+                        scene
+                            .bundles
+                            .push((mouse_position_world.0, Handle::weak(id)));
                     }
                 }
             });
         });
     });
 }
+
+fn ui_scene(
+    egui_context: ResMut<EguiContext>,
+    mut scene: ResMut<EditorScene>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    current_level: Res<CurrentLevel>,
+    handles: Res<Handles>,
+    levels: Res<Assets<Level>>,
+    sceneries: Res<Assets<Scenery>>,
+    bundles: Res<Assets<ItemBundle>>,
+    items: Query<Entity, With<IsItem>>,
+) {
+    let ctx = egui_context.ctx();
+    egui::Window::new("Scene").show(ctx, |ui| {
+        egui::Grid::new("scene_grid").show(ui, |ui| {
+            ui.label("File");
+            ui.text_edit_singleline(&mut scene.path);
+            ui.end_row();
+            ui.label("Label");
+            ui.text_edit_singleline(&mut scene.label);
+            ui.end_row();
+            ui.label("Recipe");
+            ui.text_edit_singleline(&mut scene.recipe);
+            ui.end_row();
+        });
+        ui.label(format!("{} bundles placed", scene.bundles.len()));
+
+        ui.horizontal(|ui| {
+            if ui.button("From current level").clicked() {
+                if let Some(level) = levels.get(&handles.levels[current_level.0]) {
+                    scene.label = level.label.clone();
+                    scene.recipe = ron::ser::to_string(&level.recipe).unwrap();
+                    scene.cauldrons = level
+                        .cauldrons
+                        .iter()
+                        .map(|cauldron| cauldron.file.clone())
+                        .collect();
+                    scene.scenery = level.scenery.clone();
+                    scene.bundles = level.bundles.clone();
+                    scene.tools = level.tools.clone();
+                    scene.par = level.par.clone();
+                }
+            }
+            if ui.button("Clear").clicked() {
+                items.for_each(|e| commands.entity(e).despawn_recursive());
+                scene.bundles.clear();
+            }
+            if ui.button("Save").clicked() {
+                let result = scene
+                    .to_level_file(&asset_server, &sceneries, &bundles)
+                    .and_then(|level| save_level(&scene.path, level).map_err(|e| e.to_string()));
+                scene.status = match result {
+                    Ok(()) => format!("saved '{}' to {}", scene.label, scene.path),
+                    Err(e) => format!("error: {}", e),
+                };
+            }
+        });
+        if !scene.status.is_empty() {
+            ui.label(scene.status.as_str());
+        }
+    });
+}
@@ -0,0 +1,467 @@
+use bevy::{asset::HandleId, prelude::*};
+use bevy_egui::{egui, EguiContext};
+use std::fmt::Write;
+
+use super::EguiTextures;
+use crate::{
+    items::{Collider, Item, Shape},
+    material::Material,
+};
+
+const CANVAS_SIZE: f32 = 300.;
+const GRIP_RADIUS: f32 = 4.;
+// Positions are snapped to half pixels, like the hand-written ones
+const SNAP: f32 = 0.5;
+
+pub(super) struct ColliderEditor {
+    item: Option<HandleId>,
+    colliders: Vec<Collider>,
+    selected: Option<usize>,
+    dragging: Option<Grip>,
+    zoom: f32,
+    status: String,
+}
+
+impl Default for ColliderEditor {
+    fn default() -> Self {
+        Self {
+            item: None,
+            colliders: Vec::new(),
+            selected: None,
+            dragging: None,
+            zoom: 3.,
+            status: String::new(),
+        }
+    }
+}
+
+/// A point of a collider that can be dragged around.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Grip {
+    Position(usize),
+    Radius(usize),
+    HalfExtents(usize),
+    Vertex(usize, usize),
+}
+
+impl Grip {
+    fn collider(&self) -> usize {
+        match *self {
+            Grip::Position(i) | Grip::Radius(i) | Grip::HalfExtents(i) | Grip::Vertex(i, _) => i,
+        }
+    }
+}
+
+fn grips(colliders: &[Collider]) -> Vec<(Grip, Vec2)> {
+    let mut grips = Vec::new();
+    for (i, collider) in colliders.iter().enumerate() {
+        let position = &collider.position;
+        match &collider.shape {
+            Shape::Ball(radius) => {
+                grips.push((Grip::Position(i), *position));
+                grips.push((Grip::Radius(i), *position + Vec2::new(*radius, 0.)));
+            }
+            Shape::Cuboid(half_extents) | Shape::RoundCuboid(half_extents, _) => {
+                grips.push((Grip::Position(i), *position));
+                grips.push((Grip::HalfExtents(i), *position + *half_extents));
+            }
+            Shape::ConvexPolygon(vertices) => {
+                for (j, vertex) in vertices.iter().enumerate() {
+                    grips.push((Grip::Vertex(i, j), *position + *vertex));
+                }
+            }
+        }
+    }
+    grips
+}
+
+fn move_grip(colliders: &mut [Collider], grip: Grip, to: Vec2) {
+    let collider = &mut colliders[grip.collider()];
+    let position = &mut collider.position;
+    match (grip, &mut collider.shape) {
+        (Grip::Position(_), _) => *position = to,
+        (Grip::Radius(_), Shape::Ball(radius)) => *radius = (to - *position).length().max(SNAP),
+        (Grip::HalfExtents(_), Shape::Cuboid(half_extents))
+        | (Grip::HalfExtents(_), Shape::RoundCuboid(half_extents, _)) => {
+            *half_extents = (to - *position).abs().max(Vec2::splat(SNAP))
+        }
+        (Grip::Vertex(_, j), Shape::ConvexPolygon(vertices)) => vertices[j] = to - *position,
+        _ => {}
+    }
+}
+
+fn snap(v: Vec2) -> Vec2 {
+    (v / SNAP).round() * SNAP
+}
+
+/// Writes a collider the way they are written by hand in `.items` files.
+fn write_collider(out: &mut String, collider: &Collider) {
+    let Collider {
+        position,
+        shape,
+        material,
+    } = collider;
+    let v = |v: &Vec2| format!("({:?}, {:?})", v.x, v.y);
+    let shape = match shape {
+        Shape::Ball(radius) => format!("Ball({:?})", radius),
+        Shape::Cuboid(half_extents) => format!("Cuboid({})", v(half_extents)),
+        Shape::RoundCuboid(half_extents, radius) => {
+            format!("RoundCuboid({}, {:?})", v(half_extents), radius)
+        }
+        Shape::ConvexPolygon(vertices) => format!(
+            "ConvexPolygon([{}])",
+            vertices.iter().map(v).collect::<Vec<_>>().join(", ")
+        ),
+    };
+    match material {
+        Some(material) => write!(
+            out,
+            "({}, {}, {})",
+            v(position),
+            shape,
+            write_material(material)
+        ),
+        None => write!(out, "({}, {})", v(position), shape),
+    }
+    .unwrap();
+}
+
+fn write_material(material: &Material) -> String {
+    let Material {
+        density,
+        friction,
+        restitution,
+        linear_damping,
+        angular_damping,
+        can_sleep,
+    } = material;
+    let float = |v: &Option<f32>| v.map(|v| format!("{:?}", v));
+    let mut fields = Vec::new();
+    let mut field = |name: &str, value: Option<String>| {
+        if let Some(value) = value {
+            fields.push(format!("{}: Some({})", name, value));
+        }
+    };
+    field("density", float(density));
+    field("friction", float(friction));
+    field("restitution", float(restitution));
+    field("linear_damping", float(linear_damping));
+    field("angular_damping", float(angular_damping));
+    field("can_sleep", can_sleep.map(|v| v.to_string()));
+    format!("({})", fields.join(", "))
+}
+
+/// Replaces the `colliders` list of the item labeled `label` in the text of an `.items`
+/// file, leaving everything else as it was.
+fn replace_colliders(text: &str, label: &str, colliders: &[Collider]) -> Result<String, String> {
+    let entry = text
+        .find(&format!("label: \"{}\"", label))
+        .ok_or_else(|| format!("could not find '{}'", label))?;
+    let next_entry = text[entry + 1..]
+        .find("label: \"")
+        .map_or(text.len(), |next| entry + 1 + next);
+    let key = entry
+        + text[entry..next_entry]
+            .find("colliders:")
+            .ok_or_else(|| format!("could not find the colliders of '{}'", label))?;
+
+    let start = key + text[key..].find('[').ok_or("malformed colliders")?;
+    let mut depth = 0;
+    let mut end = None;
+    for (i, c) in text[start..].char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    end = Some(start + i + 1);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let end = end.ok_or("malformed colliders")?;
+
+    let line_start = text[..key].rfind('\n').map_or(0, |i| i + 1);
+    let indent = &text[line_start..key];
+    let mut list = String::from("[\n");
+    for collider in colliders {
+        list.push_str(indent);
+        list.push_str("    ");
+        write_collider(&mut list, collider);
+        list.push_str(",\n");
+    }
+    list.push_str(indent);
+    list.push(']');
+
+    Ok(format!("{}{}{}", &text[..start], list, &text[end..]))
+}
+
+fn save(
+    asset_server: &AssetServer,
+    item: HandleId,
+    label: &str,
+    colliders: &[Collider],
+) -> Result<String, String> {
+    let path = asset_server
+        .get_handle_path(item)
+        .ok_or_else(|| format!("unknown path for item '{}'", label))?;
+    let path = std::path::Path::new("assets").join(path.path());
+    let text = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let text = replace_colliders(&text, label, colliders)?;
+    std::fs::write(&path, text).map_err(|e| e.to_string())?;
+    Ok(format!("saved '{}' to {}", label, path.display()))
+}
+
+pub(super) fn ui_colliders(
+    egui_context: Res<EguiContext>,
+    mut editor: ResMut<ColliderEditor>,
+    mut egui_textures: ResMut<EguiTextures>,
+    items: Res<Assets<Item>>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    asset_server: Res<AssetServer>,
+) {
+    let ColliderEditor {
+        item: editing,
+        colliders,
+        selected,
+        dragging,
+        zoom,
+        status,
+    } = &mut *editor;
+    egui::Window::new("Colliders").show(egui_context.ctx(), |ui| {
+        let mut item_list: Vec<_> = items.iter().collect();
+        item_list.sort_by(|(_, a), (_, b)| a.label.cmp(&b.label));
+        egui::ScrollArea::from_max_height(100.).show(ui, |ui| {
+            for (id, item) in item_list {
+                if ui
+                    .selectable_label(*editing == Some(id), &item.label)
+                    .clicked()
+                {
+                    *editing = Some(id);
+                    *colliders = item.colliders.clone();
+                    *selected = None;
+                    status.clear();
+                }
+            }
+        });
+        ui.separator();
+
+        let (id, item) = match editing.and_then(|id| Some((id, items.get(id)?))) {
+            Some(item) => item,
+            None => {
+                ui.label("Select an item to edit its colliders");
+                return;
+            }
+        };
+
+        ui.horizontal(|ui| {
+            ui.label("Zoom");
+            ui.add(egui::Slider::new(zoom, 1.0..=6.0));
+        });
+
+        // Canvas, with item space (y up) centered and scaled by `zoom`
+        let (rect, response) = ui.allocate_exact_size(
+            egui::vec2(CANVAS_SIZE, CANVAS_SIZE),
+            egui::Sense::click_and_drag(),
+        );
+        let zoom = *zoom;
+        let to_screen = |v: Vec2| rect.center() + egui::vec2(v.x, -v.y) * zoom;
+        let from_screen = |p: egui::Pos2| {
+            let v = (p - rect.center()) / zoom;
+            Vec2::new(v.x, -v.y)
+        };
+
+        // No preview while `texture_index` is out of range, which the checker reports
+        let texture = texture_atlases
+            .get(&item.texture_atlas)
+            .and_then(|atlas| Some((atlas, atlas.textures.get(item.texture_index as usize)?)));
+        if let Some((texture_atlas, tex_rect)) = texture {
+            let tex_uv = egui::Rect {
+                min: <[f32; 2]>::from(tex_rect.min / texture_atlas.size).into(),
+                max: <[f32; 2]>::from(tex_rect.max / texture_atlas.size).into(),
+            };
+            let size = egui::vec2(tex_rect.width(), tex_rect.height()) * zoom;
+            let image_rect = egui::Rect::from_center_size(rect.center(), size);
+            let tex = egui_textures.get(texture_atlas.texture.clone_weak());
+            ui.put(
+                image_rect,
+                egui::widgets::Image::new(egui::TextureId::User(tex), size).uv(tex_uv),
+            );
+        }
+
+        let painter = ui.painter_at(rect);
+        for (i, collider) in colliders.iter().enumerate() {
+            let position = &collider.position;
+            let color = if *selected == Some(i) {
+                egui::Color32::YELLOW
+            } else {
+                egui::Color32::GREEN
+            };
+            let stroke = egui::Stroke::new(1., color);
+            match &collider.shape {
+                Shape::Ball(radius) => {
+                    painter.circle_stroke(to_screen(*position), radius * zoom, stroke)
+                }
+                Shape::Cuboid(half_extents) => painter.rect_stroke(
+                    egui::Rect::from_center_size(
+                        to_screen(*position),
+                        egui::vec2(half_extents.x, half_extents.y) * 2. * zoom,
+                    ),
+                    0.,
+                    stroke,
+                ),
+                Shape::RoundCuboid(half_extents, radius) => painter.rect_stroke(
+                    egui::Rect::from_center_size(
+                        to_screen(*position),
+                        (egui::vec2(half_extents.x, half_extents.y) + egui::Vec2::splat(*radius))
+                            * 2.
+                            * zoom,
+                    ),
+                    radius * zoom,
+                    stroke,
+                ),
+                Shape::ConvexPolygon(vertices) => {
+                    for (a, b) in vertices.iter().zip(vertices.iter().cycle().skip(1)) {
+                        painter.line_segment(
+                            [to_screen(*position + *a), to_screen(*position + *b)],
+                            stroke,
+                        );
+                    }
+                }
+            }
+        }
+        for (_, point) in grips(colliders) {
+            painter.circle_filled(to_screen(point), GRIP_RADIUS, egui::Color32::WHITE);
+        }
+
+        // Dragging grips
+        let pointer = ui.input().pointer.interact_pos();
+        if let Some(pointer) = pointer {
+            if response.drag_started() {
+                *dragging = grips(colliders)
+                    .into_iter()
+                    .map(|(grip, point)| (grip, to_screen(point).distance(pointer)))
+                    .filter(|(_, distance)| *distance <= GRIP_RADIUS * 2.)
+                    .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                    .map(|(grip, _)| grip);
+                if let Some(grip) = *dragging {
+                    *selected = Some(grip.collider());
+                }
+            }
+            if response.dragged() {
+                if let Some(grip) = *dragging {
+                    move_grip(colliders, grip, snap(from_screen(pointer)));
+                }
+            }
+        }
+        if response.drag_released() {
+            *dragging = None;
+        }
+
+        // Shape list
+        let mut remove = None;
+        for (i, Collider { shape, .. }) in colliders.iter().enumerate() {
+            ui.horizontal(|ui| {
+                let name = match shape {
+                    Shape::Ball(_) => "Ball",
+                    Shape::Cuboid(_) => "Cuboid",
+                    Shape::RoundCuboid(_, _) => "RoundCuboid",
+                    Shape::ConvexPolygon(_) => "ConvexPolygon",
+                };
+                if ui
+                    .selectable_label(*selected == Some(i), format!("{}. {}", i, name))
+                    .clicked()
+                {
+                    *selected = Some(i);
+                }
+                if ui.small_button("Remove").clicked() {
+                    remove = Some(i);
+                }
+                if let Err(e) = shape.check() {
+                    ui.colored_label(egui::Color32::RED, e);
+                }
+            });
+        }
+        if let Some(i) = remove {
+            colliders.remove(i);
+            *selected = None;
+        }
+
+        if let Some(Collider { shape, .. }) = selected.and_then(|i| colliders.get_mut(i)) {
+            match shape {
+                Shape::RoundCuboid(_, radius) => {
+                    ui.horizontal(|ui| {
+                        ui.label("Border radius");
+                        ui.add(egui::DragValue::new(radius).speed(SNAP));
+                    });
+                }
+                Shape::ConvexPolygon(vertices) => {
+                    ui.horizontal(|ui| {
+                        if ui.button("Add vertex").clicked() {
+                            // Split the longest edge
+                            let n = vertices.len();
+                            let longest = (0..n)
+                                .max_by(|&a, &b| {
+                                    let length =
+                                        |i: usize| (vertices[(i + 1) % n] - vertices[i]).length();
+                                    length(a).partial_cmp(&length(b)).unwrap()
+                                })
+                                .unwrap();
+                            let middle = (vertices[longest] + vertices[(longest + 1) % n]) / 2.;
+                            vertices.insert(longest + 1, snap(middle));
+                        }
+                        if vertices.len() > 3 && ui.button("Remove vertex").clicked() {
+                            vertices.pop();
+                        }
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        ui.horizontal(|ui| {
+            let clicked = [
+                ui.button("Add ball").clicked(),
+                ui.button("Add cuboid").clicked(),
+                ui.button("Add polygon").clicked(),
+            ];
+            let new_shape = match clicked {
+                [true, _, _] => Some(Shape::Ball(10.)),
+                [_, true, _] => Some(Shape::Cuboid(Vec2::splat(10.))),
+                [_, _, true] => Some(Shape::ConvexPolygon(vec![
+                    Vec2::new(-10., -10.),
+                    Vec2::new(10., -10.),
+                    Vec2::new(0., 10.),
+                ])),
+                _ => None,
+            };
+            if let Some(shape) = new_shape {
+                colliders.push(Collider {
+                    position: Vec2::ZERO,
+                    shape,
+                    material: None,
+                });
+                *selected = Some(colliders.len() - 1);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Revert").clicked() {
+                *colliders = item.colliders.clone();
+                *selected = None;
+                status.clear();
+            }
+            if ui.button("Save").clicked() {
+                *status = match save(&asset_server, id, &item.label, colliders) {
+                    Ok(status) => status,
+                    Err(e) => format!("error: {}", e),
+                };
+            }
+        });
+        if !status.is_empty() {
+            ui.label(status.as_str());
+        }
+    });
+}
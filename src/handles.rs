@@ -1,31 +1,37 @@
 use bevy::{
     asset::{AssetServer, Assets, Handle},
     ecs::world::FromWorld,
-    render::texture::Texture,
+    render::{color::Color, texture::Texture},
     sprite::{ColorMaterial, TextureAtlas},
 };
 
 use crate::{
     items::{Item, ItemBundle},
     levels::Level,
+    reactions::{Reactions, REACTIONS_FILE},
+    tools::{Tools, TOOLS_FILE},
 };
 
 pub struct Handles {
     pub bg_material: Handle<ColorMaterial>,
     pub leg_texture: Handle<Texture>,
-    pub leg_material: Handle<ColorMaterial>,
-    pub cauldron_material: Handle<ColorMaterial>,
+    pub placeholder_material: Handle<ColorMaterial>,
     pub controls_atlas: Handle<TextureAtlas>,
     pub items_atlas: Handle<TextureAtlas>,
     pub smoke_atlas: Handle<TextureAtlas>,
     pub items: Handle<Item>,
     pub item_bundles: Handle<ItemBundle>,
     pub levels: Vec<Handle<Level>>,
+    pub reactions: Handle<Reactions>,
+    pub tools: Handle<Tools>,
 }
 
 impl FromWorld for Handles {
     fn from_world(world: &mut bevy::prelude::World) -> Self {
         let asset_server = world.get_resource::<AssetServer>().unwrap();
+        // Reload assets when their files change; this has to come before any loads
+        #[cfg(not(target_arch = "wasm32"))]
+        asset_server.watch_for_changes().unwrap();
         let mut color_materials = unsafe {
             world
                 .get_resource_unchecked_mut::<Assets<ColorMaterial>>()
@@ -47,19 +53,23 @@ impl FromWorld for Handles {
             asset_server.load("levels.levels#level5"),
         ];
 
+        let reactions = asset_server.load(REACTIONS_FILE);
+        let tools = asset_server.load(TOOLS_FILE);
+
         asset_server.load_folder("").unwrap();
 
         Handles {
             bg_material: color_materials.add(asset_server.load("main.png").into()),
             leg_texture: asset_server.load("leg.png"),
-            leg_material: color_materials.add(asset_server.load("leg.png").into()),
-            cauldron_material: color_materials.add(asset_server.load("cauldron.png").into()),
+            placeholder_material: color_materials.add(Color::FUCHSIA.into()),
             controls_atlas: controls_atlas,
             items_atlas: items_atlas,
             smoke_atlas: smoke_atlas,
             items,
             item_bundles,
             levels,
+            reactions,
+            tools,
         }
     }
 }
@@ -0,0 +1,282 @@
+use bevy::{
+    input::{
+        gamepad::{GamepadEvent, GamepadEventType},
+        InputSystem,
+    },
+    prelude::*,
+};
+use bevy_egui::egui;
+use std::collections::HashSet;
+
+use crate::{mouse_position_world::MousePositionWorld, HoofTarget};
+
+/// The part of the world the camera shows, from the center to a corner.
+const VIEW_EXTENTS: [f32; 2] = [200., 150.];
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Input<Action>>()
+            .init_resource::<InputSettings>()
+            .init_resource::<Gamepads>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                connect_gamepads
+                    .system()
+                    .label("connect_gamepads")
+                    .after(InputSystem),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_actions.system().after("connect_gamepads"),
+            );
+    }
+}
+
+/// What the player can do, whatever they do it with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Steer the hoof, or move through menus.
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Press the menu button in focus.
+    Confirm,
+    /// Leave the level or the menu page.
+    Back,
+    Restart,
+    /// Hold on with tools that grab.
+    Grab,
+    NextTool,
+}
+
+pub struct InputSettings {
+    /// Units per second, when steering the hoof without the mouse.
+    pub hoof_speed: f32,
+    /// How far a stick has to be pushed before it steers.
+    pub dead_zone: f32,
+    pub keys: Vec<(KeyCode, Action)>,
+    pub mouse_buttons: Vec<(MouseButton, Action)>,
+    pub gamepad_buttons: Vec<(GamepadButtonType, Action)>,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        use Action::*;
+        Self {
+            hoof_speed: 300.,
+            dead_zone: 0.15,
+            keys: vec![
+                (KeyCode::Up, Up),
+                (KeyCode::W, Up),
+                (KeyCode::Down, Down),
+                (KeyCode::S, Down),
+                (KeyCode::Left, Left),
+                (KeyCode::A, Left),
+                (KeyCode::Right, Right),
+                (KeyCode::D, Right),
+                (KeyCode::Return, Confirm),
+                (KeyCode::Escape, Back),
+                (KeyCode::Back, Back),
+                (KeyCode::R, Restart),
+                (KeyCode::Space, Grab),
+                (KeyCode::Tab, NextTool),
+            ],
+            mouse_buttons: vec![(MouseButton::Left, Grab)],
+            gamepad_buttons: vec![
+                (GamepadButtonType::DPadUp, Up),
+                (GamepadButtonType::DPadDown, Down),
+                (GamepadButtonType::DPadLeft, Left),
+                (GamepadButtonType::DPadRight, Right),
+                (GamepadButtonType::South, Confirm),
+                (GamepadButtonType::Select, Back),
+                (GamepadButtonType::West, Restart),
+                (GamepadButtonType::RightTrigger2, Grab),
+                (GamepadButtonType::RightTrigger, NextTool),
+            ],
+        }
+    }
+}
+
+/// The gamepads that are connected.
+#[derive(Default)]
+pub struct Gamepads(HashSet<Gamepad>);
+
+fn connect_gamepads(mut gamepads: ResMut<Gamepads>, mut events: EventReader<GamepadEvent>) {
+    for GamepadEvent(gamepad, event) in events.iter() {
+        match event {
+            GamepadEventType::Connected => {
+                gamepads.0.insert(*gamepad);
+            }
+            GamepadEventType::Disconnected => {
+                gamepads.0.remove(gamepad);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The left stick of the first gamepad that has it pushed past the dead zone.
+fn stick(gamepads: &Gamepads, axes: &Axis<GamepadAxis>, dead_zone: f32) -> Option<Vec2> {
+    gamepads
+        .0
+        .iter()
+        .map(|&gamepad| {
+            let axis = |ty| axes.get(GamepadAxis(gamepad, ty)).unwrap_or(0.);
+            Vec2::new(
+                axis(GamepadAxisType::LeftStickX),
+                axis(GamepadAxisType::LeftStickY),
+            )
+        })
+        .find(|stick| stick.length() > dead_zone)
+}
+
+fn update_actions(
+    mut actions: ResMut<Input<Action>>,
+    settings: Res<InputSettings>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+) {
+    let mut held: HashSet<Action> = HashSet::new();
+    held.extend(
+        settings
+            .keys
+            .iter()
+            .filter(|(key, _)| keys.pressed(*key))
+            .map(|(_, action)| *action),
+    );
+    held.extend(
+        settings
+            .mouse_buttons
+            .iter()
+            .filter(|(button, _)| mouse_buttons.pressed(*button))
+            .map(|(_, action)| *action),
+    );
+    for &gamepad in &gamepads.0 {
+        held.extend(
+            settings
+                .gamepad_buttons
+                .iter()
+                .filter(|(button, _)| gamepad_buttons.pressed(GamepadButton(gamepad, *button)))
+                .map(|(_, action)| *action),
+        );
+    }
+    // Pushing the stick most of the way also counts, for menus
+    if let Some(stick) = stick(&gamepads, &axes, 0.5) {
+        if stick.x.abs() > stick.y.abs() {
+            held.insert(if stick.x > 0. {
+                Action::Right
+            } else {
+                Action::Left
+            });
+        } else {
+            held.insert(if stick.y > 0. {
+                Action::Up
+            } else {
+                Action::Down
+            });
+        }
+    }
+
+    actions.update();
+    let released: Vec<Action> = actions
+        .get_pressed()
+        .filter(|action| !held.contains(action))
+        .copied()
+        .collect();
+    for action in released {
+        actions.release(action);
+    }
+    for action in held {
+        actions.press(action);
+    }
+}
+
+/// Moves the hoof target to the mouse when it moves, or else steers it with the
+/// direction actions or a stick.
+pub fn steer_hoof(
+    time: Res<Time>,
+    settings: Res<InputSettings>,
+    actions: Res<Input<Action>>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    mouse_position_world: Res<MousePositionWorld>,
+    mut hoof_target: ResMut<HoofTarget>,
+) {
+    if cursor_moved.iter().last().is_some() {
+        hoof_target.0 = mouse_position_world.0;
+        return;
+    }
+
+    let direction = match stick(&gamepads, &axes, settings.dead_zone) {
+        Some(stick) => stick,
+        None => {
+            let pressed = |action| actions.pressed(action) as i32 as f32;
+            Vec2::new(
+                pressed(Action::Right) - pressed(Action::Left),
+                pressed(Action::Up) - pressed(Action::Down),
+            )
+        }
+    };
+    // Diagonals aren't any faster
+    let direction = if direction.length() > 1. {
+        direction.normalize()
+    } else {
+        direction
+    };
+    let extents = Vec2::from(VIEW_EXTENTS);
+    hoof_target.0 = (hoof_target.0 + direction * settings.hoof_speed * time.delta_seconds())
+        .max(-extents)
+        .min(extents);
+}
+
+/// Moves the focus through the buttons of a menu screen with the `Up` and `Down`
+/// actions, and presses the one in focus with `Confirm`, for playing without a mouse.
+#[derive(Default)]
+pub struct MenuFocus {
+    index: usize,
+    /// How many buttons there were last frame, and so far this frame.
+    count: usize,
+    buttons: usize,
+    confirm: bool,
+}
+
+impl MenuFocus {
+    /// Call every frame before showing the buttons.
+    pub fn update(&mut self, actions: &Input<Action>) {
+        self.count = self.buttons;
+        self.buttons = 0;
+        if self.count > 0 {
+            if actions.just_pressed(Action::Down) {
+                self.index = (self.index + 1) % self.count;
+            }
+            if actions.just_pressed(Action::Up) {
+                self.index = (self.index + self.count - 1) % self.count;
+            }
+            self.index = self.index.min(self.count - 1);
+        }
+        self.confirm = actions.just_pressed(Action::Confirm);
+    }
+
+    /// Back to the first button, for when the screen changes.
+    pub fn reset(&mut self) {
+        self.index = 0;
+    }
+
+    /// A button that is clicked with the mouse, or pressed while in focus.
+    pub fn button(&mut self, ui: &mut egui::Ui, text: &str) -> bool {
+        let focused = self.buttons == self.index;
+        self.buttons += 1;
+        let response = ui.button(text);
+        if focused {
+            response.request_focus();
+        }
+        response.clicked() || (focused && self.confirm)
+    }
+}
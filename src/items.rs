@@ -1,13 +1,30 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use bevy::{
-    asset::{AssetLoader, AssetPath, LoadedAsset},
-    ecs::system::{Command, CommandQueue, EntityCommands},
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    ecs::{
+        system::{Command, CommandQueue, EntityCommands},
+        world::FromWorld,
+    },
     prelude::*,
     reflect::TypeUuid,
     utils::BoxedFuture,
 };
 use bevy_rapier2d::{na, prelude::*};
-use serde::Deserialize;
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{collections::HashMap, convert::TryFrom, fmt};
+
+use crate::{
+    asset_errors::AssetErrors,
+    asset_files::{inline_label, parse_entries, set_labeled_asset, AssetRef, Entries},
+    breakable::Breakable,
+    joints::Joint,
+    material::Material,
+    ItemType, LevelEntity, UpdateRecipeEvent,
+};
 
 fn is_clockwise(vertices: &[Vec2]) -> bool {
     (vertices
@@ -18,24 +35,94 @@ fn is_clockwise(vertices: &[Vec2]) -> bool {
         < 0.0
 }
 
-#[derive(Debug, Clone, Deserialize)]
-enum Shape {
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub(crate) enum Shape {
     Ball(f32),
     Cuboid(Vec2),
     RoundCuboid(Vec2, f32),
     ConvexPolygon(Vec<Vec2>),
 }
 
-impl Into<ColliderShape> for Shape {
-    fn into(self) -> ColliderShape {
+impl Shape {
+    /// Catches what would make the collider panic or misbehave when built.
+    pub(crate) fn check(&self) -> Result<(), String> {
         match self {
-            Shape::Ball(radius) => ColliderShape::ball(radius),
+            Shape::Ball(radius) if *radius <= 0. => {
+                Err(format!("ball radius {} is not positive", radius))
+            }
+            Shape::Cuboid(half_extents) | Shape::RoundCuboid(half_extents, _)
+                if half_extents.x <= 0. || half_extents.y <= 0. =>
+            {
+                Err(format!(
+                    "cuboid half extents {} are not positive",
+                    half_extents
+                ))
+            }
+            Shape::ConvexPolygon(vertices) => {
+                if vertices.len() < 3 {
+                    return Err(format!("polygon has only {} vertices", vertices.len()));
+                }
+                let n = vertices.len();
+                let turns: Vec<(Vec2, Vec2)> = (0..n)
+                    .map(|i| {
+                        let (a, b, c) = (vertices[i], vertices[(i + 1) % n], vertices[(i + 2) % n]);
+                        (b - a, c - b)
+                    })
+                    .collect();
+                let crosses: Vec<f32> = turns.iter().map(|(u, v)| u.perp_dot(*v)).collect();
+                // A star turns the same way at every vertex too, but goes around more
+                // than once
+                let turning: f32 = turns
+                    .iter()
+                    .map(|(u, v)| u.perp_dot(*v).atan2(u.dot(*v)))
+                    .sum();
+                if crosses.iter().all(|cross| cross.abs() < 1e-3) {
+                    Err("polygon is degenerate".to_string())
+                } else if crosses.iter().any(|cross| *cross > 1e-3)
+                    && crosses.iter().any(|cross| *cross < -1e-3)
+                {
+                    Err("polygon is not convex".to_string())
+                } else if (turning.abs() - std::f32::consts::TAU).abs() > 1e-2 {
+                    Err("polygon crosses itself".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Fails on the first of `shapes` that `Shape::check` rejects, naming the asset labeled
+/// `label` that it belongs to.
+pub(crate) fn check_shapes<'a>(
+    shapes: impl IntoIterator<Item = &'a Shape>,
+    label: &str,
+) -> Result<()> {
+    for shape in shapes {
+        if let Err(e) = shape.check() {
+            match label {
+                "" => bail!("{}", e),
+                label => bail!("'{}': {}", label, e),
+            }
+        }
+    }
+    Ok(())
+}
+
+impl TryFrom<&Shape> for ColliderShape {
+    type Error = String;
+
+    fn try_from(shape: &Shape) -> Result<Self, String> {
+        shape.check()?;
+        Ok(match shape {
+            Shape::Ball(radius) => ColliderShape::ball(*radius),
             Shape::Cuboid(half_extents) => ColliderShape::cuboid(half_extents.x, half_extents.y),
             Shape::RoundCuboid(half_extents, radius) => {
-                ColliderShape::round_cuboid(half_extents.x, half_extents.y, radius)
+                ColliderShape::round_cuboid(half_extents.x, half_extents.y, *radius)
             }
             Shape::ConvexPolygon(vertices) => {
-                ColliderShape::convex_polyline(if is_clockwise(&vertices) {
+                ColliderShape::convex_polyline(if is_clockwise(vertices) {
                     vertices
                         .iter()
                         .rev()
@@ -47,20 +134,110 @@ impl Into<ColliderShape> for Shape {
                         .map(|v| na::Point2::new(v.x, v.y))
                         .collect::<Vec<_>>()
                 })
-                .unwrap()
+                .ok_or("polygon has no convex hull")?
+            }
+        })
+    }
+}
+
+/// Builds the shape of a collider, or skips it with an error if it can't be. Loaders
+/// reject such shapes, so this only happens to colliders made some other way.
+pub(crate) fn collider_shape(shape: &Shape) -> Option<ColliderShape> {
+    ColliderShape::try_from(shape)
+        .map_err(|e| eprintln!("error: skipping collider: {}", e))
+        .ok()
+}
+
+/// One shape of an item, written `(position, shape)`, or `(position, shape, material)`
+/// when it doesn't use the item's material.
+#[derive(Debug, Clone)]
+pub(crate) struct Collider {
+    pub position: Vec2,
+    pub shape: Shape,
+    pub material: Option<Material>,
+}
+
+impl<'de> Deserialize<'de> for Collider {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ColliderVisitor;
+
+        impl<'de> Visitor<'de> for ColliderVisitor {
+            type Value = Collider;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a (position, shape) or (position, shape, material) tuple")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let position = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let shape = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                let material = seq.next_element()?;
+                Ok(Collider {
+                    position,
+                    shape,
+                    material,
+                })
             }
         }
+
+        deserializer.deserialize_tuple(3, ColliderVisitor)
     }
 }
 
-#[derive(Deserialize)]
-struct ItemFile {
-    label: String,
+impl Serialize for Collider {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let len = if self.material.is_some() { 3 } else { 2 };
+        let mut tuple = serializer.serialize_tuple(len)?;
+        tuple.serialize_element(&self.position)?;
+        tuple.serialize_element(&self.shape)?;
+        if let Some(material) = &self.material {
+            tuple.serialize_element(material)?;
+        }
+        tuple.end()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct ItemFile {
+    #[serde(default)]
+    pub label: String,
     #[serde(rename = "type")]
-    ty: String,
-    texture_atlas: String,
-    texture_index: u32,
-    colliders: Vec<(Vec2, Shape)>,
+    pub ty: String,
+    pub texture_atlas: String,
+    pub texture_index: u32,
+    #[serde(default)]
+    pub icon: bool,
+    #[serde(default, skip_serializing_if = "Material::is_empty")]
+    pub material: Material,
+    pub colliders: Vec<Collider>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breakable: Option<BreakableFile>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct BreakableFile {
+    pub threshold: f32,
+    #[serde(default)]
+    pub fragments: Vec<(Vec2, AssetRef<ItemFile>)>,
+}
+
+/// The compound of the colliders that can be built, if there are any.
+pub(crate) fn compound_shape<'a>(
+    colliders: impl IntoIterator<Item = &'a Collider>,
+) -> Option<ColliderShape> {
+    let shapes: Vec<_> = colliders
+        .into_iter()
+        .filter_map(|collider| Some((collider.position.into(), collider_shape(&collider.shape)?)))
+        .collect();
+    if shapes.is_empty() {
+        None
+    } else {
+        Some(ColliderShape::compound(shapes))
+    }
 }
 
 #[derive(Debug, Clone, TypeUuid)]
@@ -70,18 +247,14 @@ pub struct Item {
     pub ty: String,
     pub texture_atlas: Handle<TextureAtlas>,
     pub texture_index: u32,
-    colliders: Vec<(Vec2, Shape)>,
+    pub icon: bool,
+    pub(crate) material: Material,
+    pub(crate) colliders: Vec<Collider>,
+    pub(crate) breakable: Option<Breakable>,
 }
 
 impl Item {
     pub fn spawn(&self, commands: &mut EntityCommands, position: Vec2) {
-        let shape = ColliderShape::compound(
-            self.colliders
-                .iter()
-                .cloned()
-                .map(|(pos, shape)| (pos.into(), shape.into()))
-                .collect(),
-        );
         commands
             .insert_bundle(SpriteSheetBundle {
                 sprite: TextureAtlasSprite::new(self.texture_index),
@@ -91,21 +264,126 @@ impl Item {
             })
             .insert_bundle(RigidBodyBundle {
                 position: position.into(),
-                ..Default::default()
-            })
-            .insert_bundle(ColliderBundle {
-                shape,
+                damping: self.material.damping().into(),
+                activation: self.material.activation().into(),
                 ..Default::default()
             })
             .insert(RigidBodyPositionSync::Discrete)
             .insert(crate::IsItem(self.ty.clone()));
+        if let Some(breakable) = &self.breakable {
+            commands.insert(breakable.clone());
+        }
+        insert_colliders(commands, &self.colliders, &self.material);
+    }
+}
+
+/// Colliders using the body's `material` share one compound collider on the body's
+/// entity; the others each get a child entity with their own material.
+pub(crate) fn insert_colliders(
+    commands: &mut EntityCommands,
+    colliders: &[Collider],
+    material: &Material,
+) {
+    let (shared, own): (Vec<_>, Vec<_>) = colliders
+        .iter()
+        .partition(|collider| collider.material.is_none());
+    if let Some(shape) = compound_shape(shared) {
+        commands.insert_bundle(ColliderBundle {
+            shape,
+            material: material.collider_material().into(),
+            mass_properties: material.mass_properties().into(),
+            ..Default::default()
+        });
     }
+
+    let body = commands.id().handle();
+    commands.with_children(|parent| {
+        for collider in own {
+            let shape = match collider_shape(&collider.shape) {
+                Some(shape) => shape,
+                None => continue,
+            };
+            let material = collider.material.unwrap_or_default().or(material);
+            parent
+                .spawn_bundle(ColliderBundle {
+                    shape,
+                    material: material.collider_material().into(),
+                    mass_properties: material.mass_properties().into(),
+                    ..Default::default()
+                })
+                .insert(ColliderParent {
+                    handle: body,
+                    pos_wrt_parent: collider.position.into(),
+                });
+        }
+    });
+}
+
+#[derive(Debug, Clone)]
+pub struct ItemTypeInfo {
+    pub label: String,
+    pub texture_atlas: Handle<TextureAtlas>,
+    pub texture_index: u32,
 }
 
-#[derive(Deserialize)]
-struct ItemBundleFile {
-    label: String,
-    items: Vec<(Vec2, String)>,
+#[derive(Default)]
+pub struct ItemTypes(HashMap<ItemType, ItemTypeInfo>);
+
+impl ItemTypes {
+    pub fn get(&self, ty: &str) -> Option<&ItemTypeInfo> {
+        self.0.get(ty)
+    }
+}
+
+/// Rebuilds the item type registry whenever an item is loaded, changed or removed.
+///
+/// Each type is displayed with the sprite of the item marked as its `icon`, or,
+/// failing that, of the item with the lowest texture index.
+pub fn update_item_types(
+    mut item_events: EventReader<AssetEvent<Item>>,
+    mut update_recipe_events: EventWriter<UpdateRecipeEvent>,
+    mut item_types: ResMut<ItemTypes>,
+    items: Res<Assets<Item>>,
+) {
+    if item_events.iter().count() == 0 {
+        return;
+    }
+
+    let mut icons: HashMap<ItemType, &Item> = HashMap::new();
+    for (_, item) in items.iter() {
+        let is_better = match icons.get(&item.ty) {
+            Some(icon) => (!item.icon, item.texture_index) < (!icon.icon, icon.texture_index),
+            None => true,
+        };
+        if is_better {
+            icons.insert(item.ty.clone(), item);
+        }
+    }
+
+    item_types.0 = icons
+        .into_iter()
+        .map(|(ty, item)| {
+            (
+                ty,
+                ItemTypeInfo {
+                    label: item.label.clone(),
+                    texture_atlas: item.texture_atlas.clone(),
+                    texture_index: item.texture_index,
+                },
+            )
+        })
+        .collect();
+
+    update_recipe_events.send(UpdateRecipeEvent);
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct ItemBundleFile {
+    #[serde(default)]
+    pub label: String,
+    pub items: Vec<(Vec2, AssetRef<ItemFile>)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub joints: Vec<Joint>,
 }
 
 #[derive(Debug, Clone, TypeUuid)]
@@ -113,21 +391,77 @@ struct ItemBundleFile {
 pub struct ItemBundle {
     pub label: String,
     pub items: Vec<(Vec2, Handle<Item>)>,
+    pub(crate) joints: Vec<Joint>,
+    /// What it was loaded from, for the editor to write it back.
+    pub(crate) file: ItemBundleFile,
 }
 
 impl ItemBundle {
     pub fn spawn(&self, commands: &mut Commands, items: &Assets<Item>, position: Vec2) {
-        for (offset, item) in &self.items {
-            items
-                .get(item)
-                .unwrap()
-                .spawn(&mut commands.spawn(), position + *offset);
+        let entities: Vec<Option<Entity>> = self
+            .items
+            .iter()
+            .map(|(offset, item)| match items.get(item) {
+                Some(item) => {
+                    let mut entity = commands.spawn();
+                    item.spawn(&mut entity, position + *offset);
+                    Some(entity.id())
+                }
+                None => {
+                    eprintln!("error: could not find item '{:?}'", item);
+                    None
+                }
+            })
+            .collect();
+
+        for joint in &self.joints {
+            let member = |index: usize| {
+                let entity = entities.get(index).copied().flatten()?;
+                Some((entity, joint.anchor - self.items[index].0))
+            };
+            let item = match member(joint.item) {
+                Some(item) => item,
+                None => continue,
+            };
+            let other = match joint.other {
+                Some(other) => match member(other) {
+                    Some(other) => other,
+                    None => continue,
+                },
+                // The world, as a body right at the anchor
+                None => {
+                    let world = commands
+                        .spawn_bundle(RigidBodyBundle {
+                            body_type: RigidBodyType::Static,
+                            position: (position + joint.anchor).into(),
+                            ..Default::default()
+                        })
+                        .insert(LevelEntity)
+                        .id();
+                    (world, Vec2::ZERO)
+                }
+            };
+
+            let params = joint.params(other.1, item.1);
+            commands
+                .spawn()
+                .insert(JointBuilderComponent::new(params, other.0, item.0))
+                .insert(LevelEntity);
         }
     }
 }
 
-#[derive(Clone, Default)]
-pub struct ItemLoader;
+pub struct ItemLoader {
+    errors: AssetErrors,
+}
+
+impl FromWorld for ItemLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            errors: world.get_resource::<AssetErrors>().unwrap().clone(),
+        }
+    }
+}
 
 const FILE_EXTENSIONS: &[&str] = &["items"];
 
@@ -138,31 +472,9 @@ impl AssetLoader for ItemLoader {
         load_context: &'a mut bevy::asset::LoadContext<'_>,
     ) -> BoxedFuture<'a, Result<()>> {
         Box::pin(async move {
-            // TODO single-item file?
-            let items: Vec<ItemFile> = ron::de::from_bytes(bytes)?;
-            items.into_iter().for_each(|item| {
-                let ItemFile {
-                    label,
-                    ty,
-                    texture_atlas,
-                    texture_index,
-                    colliders,
-                } = item;
-
-                let texture_atlas: Handle<TextureAtlas> =
-                    load_context.get_handle(AssetPath::from(texture_atlas.as_str()).get_id());
-
-                let asset = Item {
-                    label: label.clone(),
-                    ty,
-                    texture_atlas,
-                    texture_index,
-                    colliders,
-                };
-                load_context.set_labeled_asset(&label, LoadedAsset::new(asset));
-            });
-
-            Ok(())
+            let result = load_items(bytes, load_context);
+            self.errors.report(load_context.path(), &result);
+            result
         })
     }
 
@@ -171,8 +483,100 @@ impl AssetLoader for ItemLoader {
     }
 }
 
-#[derive(Clone, Default)]
-pub struct ItemBundleLoader;
+fn load_items(bytes: &[u8], load_context: &mut LoadContext) -> Result<()> {
+    match parse_entries::<ItemFile>(bytes)? {
+        Entries::One(item) => {
+            let asset = item_asset(item, "", load_context)?;
+            load_context.set_default_asset(LoadedAsset::new(asset));
+        }
+        Entries::Many(items) => {
+            for item in items {
+                let label = item.label.clone();
+                let asset = item_asset(item, &label, load_context)?;
+                set_labeled_asset(load_context, &label, asset)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds an item asset, adding its inline fragments as assets labeled under `parent`.
+fn item_asset(item: ItemFile, parent: &str, load_context: &mut LoadContext) -> Result<Item> {
+    let ItemFile {
+        label,
+        ty,
+        texture_atlas,
+        texture_index,
+        icon,
+        material,
+        colliders,
+        breakable,
+    } = item;
+
+    check_shapes(colliders.iter().map(|collider| &collider.shape), parent)?;
+    let texture_atlas: Handle<TextureAtlas> =
+        load_context.get_handle(AssetPath::from(texture_atlas.as_str()).get_id());
+
+    let breakable = match breakable {
+        Some(breakable) => Some(Breakable {
+            threshold: breakable.threshold,
+            fragments: breakable
+                .fragments
+                .into_iter()
+                .enumerate()
+                .map(|(i, (pos, fragment))| {
+                    let fragment = item_handle(fragment, parent, i, load_context)?;
+                    Ok((pos, fragment.clone_untyped()))
+                })
+                .collect::<Result<_>>()?,
+        }),
+        None => None,
+    };
+
+    Ok(Item {
+        label,
+        ty,
+        texture_atlas,
+        texture_index,
+        icon,
+        material,
+        colliders,
+        breakable,
+    })
+}
+
+/// A handle to a referenced item, or to an inline one added as the `index`th asset
+/// labeled under `parent`.
+pub(crate) fn item_handle(
+    item: AssetRef<ItemFile>,
+    parent: &str,
+    index: usize,
+    load_context: &mut LoadContext,
+) -> Result<Handle<Item>> {
+    match item {
+        AssetRef::Path(path) => {
+            Ok(load_context.get_handle(AssetPath::from(path.as_str()).get_id()))
+        }
+        AssetRef::Inline(item) => {
+            let label = inline_label(parent, &item.label, index);
+            let asset = item_asset(item, &label, load_context)?;
+            set_labeled_asset(load_context, &label, asset)
+        }
+    }
+}
+
+pub struct ItemBundleLoader {
+    errors: AssetErrors,
+}
+
+impl FromWorld for ItemBundleLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            errors: world.get_resource::<AssetErrors>().unwrap().clone(),
+        }
+    }
+}
 
 const BUNDLE_FILE_EXTENSIONS: &[&str] = &["bundles"];
 
@@ -183,29 +587,9 @@ impl AssetLoader for ItemBundleLoader {
         load_context: &'a mut bevy::asset::LoadContext<'_>,
     ) -> BoxedFuture<'a, Result<()>> {
         Box::pin(async move {
-            // TODO single-bundle file?
-            let bundles: Vec<ItemBundleFile> = ron::de::from_bytes(bytes)?;
-            bundles.into_iter().for_each(|bundle| {
-                let ItemBundleFile { label, items } = bundle;
-
-                let items = items
-                    .into_iter()
-                    .map(|(pos, path)| {
-                        (
-                            pos,
-                            load_context.get_handle(AssetPath::from(path.as_str()).get_id()),
-                        )
-                    })
-                    .collect();
-
-                let asset = ItemBundle {
-                    label: label.clone(),
-                    items,
-                };
-                load_context.set_labeled_asset(&label, LoadedAsset::new(asset));
-            });
-
-            Ok(())
+            let result = load_bundles(bytes, load_context);
+            self.errors.report(load_context.path(), &result);
+            result
         })
     }
 
@@ -214,6 +598,51 @@ impl AssetLoader for ItemBundleLoader {
     }
 }
 
+fn load_bundles(bytes: &[u8], load_context: &mut LoadContext) -> Result<()> {
+    match parse_entries::<ItemBundleFile>(bytes)? {
+        Entries::One(bundle) => {
+            let asset = bundle_asset(bundle, "", load_context)?;
+            load_context.set_default_asset(LoadedAsset::new(asset));
+        }
+        Entries::Many(bundles) => {
+            for bundle in bundles {
+                let label = bundle.label.clone();
+                let asset = bundle_asset(bundle, &label, load_context)?;
+                set_labeled_asset(load_context, &label, asset)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds a bundle asset, adding its inline items as assets labeled under `parent`.
+pub(crate) fn bundle_asset(
+    bundle: ItemBundleFile,
+    parent: &str,
+    load_context: &mut LoadContext,
+) -> Result<ItemBundle> {
+    let file = bundle.clone();
+    let ItemBundleFile {
+        label,
+        items,
+        joints,
+    } = bundle;
+
+    let items = items
+        .into_iter()
+        .enumerate()
+        .map(|(i, (pos, item))| Ok((pos, item_handle(item, parent, i, load_context)?)))
+        .collect::<Result<_>>()?;
+
+    Ok(ItemBundle {
+        label,
+        items,
+        joints,
+        file,
+    })
+}
+
 pub struct SpawnItemBundle {
     pub bundle: Handle<ItemBundle>,
     pub position: Vec2,
@@ -231,38 +660,8 @@ impl Command for SpawnItemBundle {
             return;
         }
 
-        for (offset, item) in &bundles.get(self.bundle).unwrap().items {
-            if items.get(item).is_none() {
-                eprintln!("error: could not find item '{:?}'", item);
-                continue;
-            }
-            let item = items.get(item).unwrap();
-            let shape = ColliderShape::compound(
-                item.colliders
-                    .iter()
-                    .cloned()
-                    .map(|(pos, shape)| (pos.into(), shape.into()))
-                    .collect(),
-            );
-            commands
-                .spawn()
-                .insert_bundle(SpriteSheetBundle {
-                    sprite: TextureAtlasSprite::new(item.texture_index),
-                    texture_atlas: item.texture_atlas.clone(),
-                    transform: Transform::from_xyz(0., 0., 5.),
-                    ..Default::default()
-                })
-                .insert_bundle(RigidBodyBundle {
-                    position: (self.position + *offset).into(),
-                    ..Default::default()
-                })
-                .insert_bundle(ColliderBundle {
-                    shape,
-                    ..Default::default()
-                })
-                .insert(RigidBodyPositionSync::Discrete)
-                .insert(crate::IsItem(item.ty.clone()));
-        }
+        let bundle = bundles.get(self.bundle).unwrap();
+        bundle.spawn(&mut commands, items, self.position);
 
         command_queue.apply(world);
     }
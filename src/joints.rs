@@ -0,0 +1,140 @@
+use bevy::math::Vec2;
+use bevy_rapier2d::{na::Unit, rapier::prelude::*};
+use serde::{Deserialize, Serialize};
+
+/// A joint between two members of a bundle, or between a member and the world.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct Joint {
+    /// The index of a member of the bundle.
+    pub item: usize,
+    /// Another member, or the world if left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub other: Option<usize>,
+    /// Where the two are attached, relative to the bundle.
+    pub anchor: Vec2,
+    pub kind: JointKind,
+}
+
+/// Limits are how far `item` can turn or slide relative to `other`, or to the world.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) enum JointKind {
+    Fixed,
+    /// Turns around the anchor. Limits are angles in radians, counterclockwise.
+    Revolute {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limits: Option<(f32, f32)>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        motor: Option<Motor>,
+    },
+    /// Slides along `axis`. Limits are distances from the anchor.
+    Prismatic {
+        axis: Vec2,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limits: Option<(f32, f32)>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        motor: Option<Motor>,
+    },
+}
+
+/// Drives a joint at `velocity` (radians or units per second), pushing at most
+/// `max_impulse` per step.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub(crate) struct Motor {
+    pub velocity: f32,
+    pub max_impulse: f32,
+}
+
+impl Joint {
+    /// The joint's parameters, given where the anchor is relative to `other` (or to
+    /// the world) and to `item`. These are the first and second bodies, in that order.
+    pub fn params(&self, anchor1: Vec2, anchor2: Vec2) -> JointParams {
+        let anchor1 = Point::new(anchor1.x, anchor1.y);
+        let anchor2 = Point::new(anchor2.x, anchor2.y);
+        match &self.kind {
+            JointKind::Fixed => FixedJoint::new(
+                Isometry::translation(anchor1.x, anchor1.y),
+                Isometry::translation(anchor2.x, anchor2.y),
+            )
+            .into(),
+            JointKind::Revolute { limits, motor } => {
+                let mut joint = BallJoint::new(anchor1, anchor2);
+                if let Some((min, max)) = *limits {
+                    // Rapier limits the angle between two axes, so aim them at the middle
+                    let middle = (min + max) / 2.;
+                    joint.limits_enabled = true;
+                    joint.limits_local_axis1 =
+                        Unit::new_normalize(Vector::new(middle.cos(), middle.sin()));
+                    joint.limits_local_axis2 = Vector::x_axis();
+                    joint.limits_angle = (max - min) / 2.;
+                }
+                if let Some(motor) = motor {
+                    joint.configure_motor_velocity(motor.velocity, 1.);
+                    joint.motor_max_impulse = motor.max_impulse;
+                }
+                joint.into()
+            }
+            JointKind::Prismatic {
+                axis,
+                limits,
+                motor,
+            } => {
+                let axis = Unit::new_normalize(Vector::new(axis.x, axis.y));
+                let mut joint = PrismaticJoint::new(anchor1, axis, anchor2, axis);
+                if let Some((min, max)) = *limits {
+                    joint.limits_enabled = true;
+                    joint.limits = [min, max];
+                }
+                if let Some(motor) = motor {
+                    joint.configure_motor_velocity(motor.velocity, 1.);
+                    joint.motor_max_impulse = motor.max_impulse;
+                }
+                joint.into()
+            }
+        }
+    }
+
+    /// Catches what would make the joint panic or misbehave when built.
+    pub fn check(&self, members: usize) -> Result<(), String> {
+        for index in std::iter::once(self.item).chain(self.other) {
+            if index >= members {
+                return Err(format!(
+                    "item {} is out of range, the bundle has {} items",
+                    index, members
+                ));
+            }
+        }
+        if self.other == Some(self.item) {
+            return Err(format!("item {} is joined to itself", self.item));
+        }
+
+        let (limits, motor) = match &self.kind {
+            JointKind::Fixed => (None, None),
+            JointKind::Revolute { limits, motor } => (*limits, *motor),
+            JointKind::Prismatic {
+                axis,
+                limits,
+                motor,
+            } => {
+                if *axis == Vec2::ZERO {
+                    return Err("prismatic axis is zero".to_string());
+                }
+                (*limits, *motor)
+            }
+        };
+        if let Some((min, max)) = limits {
+            // Items are spawned where the joint is at 0
+            if min > 0. || max < 0. {
+                return Err(format!("limits ({}, {}) don't include 0", min, max));
+            }
+        }
+        if let Some(motor) = motor {
+            if motor.max_impulse < 0. {
+                return Err(format!(
+                    "motor max_impulse {} is negative",
+                    motor.max_impulse
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,37 +1,247 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use bevy::{
-    asset::{AssetLoader, AssetPath, LoadedAsset},
-    ecs::system::{Command, CommandQueue},
+    asset::{AssetLoader, AssetPath, HandleId, LoadContext, LoadState, LoadedAsset},
+    ecs::{
+        system::{Command, CommandQueue},
+        world::FromWorld,
+    },
     prelude::*,
     reflect::TypeUuid,
     utils::BoxedFuture,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::{ops::Range, path::Path};
 
 use crate::{
-    items::{ItemBundle, SpawnItemBundleExt},
-    ItemType,
+    asset_errors::AssetErrors,
+    asset_files::{
+        first_token, inline_label, parse_entries, set_labeled_asset, trivia, AssetRef, Entries,
+    },
+    cauldron::{cauldron_asset, Cauldron, CauldronFile},
+    handles::Handles,
+    items::{bundle_asset, ItemBundle, ItemBundleFile, SpawnItemBundleExt},
+    recipe::RecipeStep,
+    scenery::{scenery_asset, Scenery, SceneryFile},
+    LevelStats,
 };
 
-#[derive(Deserialize)]
-struct LevelFile {
-    label: String,
-    recipe: Vec<String>,
-    bundles: Vec<(Vec2, String)>,
+#[derive(Clone, Deserialize, Serialize)]
+pub(crate) struct LevelFile {
+    pub label: String,
+    /// The recipe of the cauldrons that don't have their own.
+    #[serde(default)]
+    pub recipe: Vec<RecipeStep>,
+    /// If there are none, the level has the default cauldron.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cauldrons: Vec<CauldronFile>,
+    /// Shelves, walls and the like, such as `"cupboard.scenery"`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scenery: Vec<(Vec2, AssetRef<SceneryFile>)>,
+    pub bundles: Vec<(Vec2, AssetRef<ItemBundleFile>)>,
+    /// Labels of the tools the player can use, or all of them if there are none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<String>,
+    #[serde(default, skip_serializing_if = "Par::is_empty")]
+    pub par: Par,
+}
+
+/// What it takes to get three stars on a level: every par that is missed costs a star,
+/// down to one.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Par {
+    /// Seconds
+    pub time: Option<f32>,
+    pub resets: Option<u32>,
+    /// Items that fell off-screen
+    pub lost: Option<u32>,
+}
+
+impl Par {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn stars(&self, stats: &LevelStats) -> u32 {
+        let missed = [
+            self.time.map_or(false, |time| stats.time > time),
+            self.resets.map_or(false, |resets| stats.resets() > resets),
+            self.lost.map_or(false, |lost| stats.lost > lost),
+        ];
+        3 - missed.iter().filter(|&&missed| missed).count().min(2) as u32
+    }
 }
 
 #[derive(Debug, Clone, TypeUuid)]
 #[uuid = "2184f3fa-2354-4d20-be9a-59cb16af498f"]
 pub struct Level {
     pub label: String,
-    pub recipe: Vec<ItemType>,
-    bundles: Vec<(Vec2, Handle<ItemBundle>)>,
+    pub recipe: Vec<RecipeStep>,
+    pub cauldrons: Vec<Cauldron>,
+    pub scenery: Vec<(Vec2, Handle<Scenery>)>,
+    pub bundles: Vec<(Vec2, Handle<ItemBundle>)>,
+    pub tools: Vec<String>,
+    pub par: Par,
 }
 
-#[derive(Clone, Default)]
-pub struct LevelLoader;
+impl Level {
+    /// The recipe of one of its cauldrons.
+    pub fn recipe_of<'a>(&'a self, cauldron: &'a Cauldron) -> &'a [RecipeStep] {
+        cauldron.file.recipe.as_ref().unwrap_or(&self.recipe)
+    }
+}
 
-const FILE_EXTENSIONS: &[&str] = &["levels"];
+/// Writes `level` to a `.levels` file, replacing any level with the same label. Only the
+/// text of that level changes, so comments and the other levels stay as they are.
+pub(crate) fn save_level(path: impl AsRef<Path>, level: LevelFile) -> Result<()> {
+    let path = path.as_ref();
+    let text = if path.exists() {
+        std::fs::read_to_string(path)?
+    } else {
+        String::new()
+    };
+    std::fs::write(path, splice_level(&text, &level)?)?;
+    Ok(())
+}
+
+fn splice_level(text: &str, level: &LevelFile) -> Result<String> {
+    let spans = EntrySpans::new(text);
+    let mut pretty = ron::ser::to_string_pretty(level, ron::ser::PrettyConfig::new())?;
+    if spans.list || spans.entries.is_empty() {
+        pretty = pretty.replace('\n', "\n    ");
+    }
+    if spans.entries.is_empty() && !spans.list {
+        return Ok(format!("{}[\n    {},\n]\n", text, pretty));
+    }
+
+    let levels = parse_entries::<LevelFile>(text.as_bytes())?;
+    if levels.iter().count() != spans.entries.len() {
+        bail!("could not tell where each level is");
+    }
+    let (span, pretty) = match levels.iter().position(|other| other.label == level.label) {
+        Some(i) => (spans.entries[i].clone(), pretty),
+        None if spans.list => {
+            let comma = if spans.comma { "," } else { "" };
+            (spans.end..spans.end, format!("{}\n    {},", comma, pretty))
+        }
+        // A single-level file stays one, so that references to it keep working
+        None => bail!(
+            "the file only holds '{}', save '{}' to another one",
+            levels.iter().next().unwrap().label,
+            level.label
+        ),
+    };
+    let (before, after) = (&text[..span.start], &text[span.end..]);
+    Ok(format!("{}{}{}", before, pretty, after))
+}
+
+/// Where the entries of a file holding one or a list of them are in its text.
+struct EntrySpans {
+    /// Whether the entries are in a list, rather than the file being a single one.
+    list: bool,
+    entries: Vec<Range<usize>>,
+    /// Where a new entry would go at the end of the list, and whether it needs a comma
+    /// before it.
+    end: usize,
+    comma: bool,
+}
+
+impl EntrySpans {
+    fn new(text: &str) -> Self {
+        let bytes = text.as_bytes();
+        let start = first_token(bytes).unwrap_or(bytes.len());
+        let list = bytes.get(start) == Some(&b'[');
+        let mut spans = Self {
+            list,
+            entries: Vec::new(),
+            end: start + list as usize,
+            comma: false,
+        };
+
+        // How deep in brackets the entries are
+        let top = list as usize;
+        let mut depth = top;
+        let mut entry = None;
+        let mut i = spans.end;
+        while i < bytes.len() {
+            let rest = &bytes[i..];
+            if let Some(skip) = trivia(rest) {
+                i += skip;
+                continue;
+            }
+            match rest[0] {
+                b',' | b')' | b']' | b'}' if depth == top => {
+                    if let Some(start) = entry.take() {
+                        spans.entries.push(start..spans.end);
+                    }
+                    if rest[0] != b',' {
+                        return spans;
+                    }
+                    spans.end = i + 1;
+                    spans.comma = false;
+                    i += 1;
+                    continue;
+                }
+                b'(' | b'[' | b'{' => depth += 1,
+                b')' | b']' | b'}' => depth -= 1,
+                _ => {}
+            }
+            entry.get_or_insert(i);
+            i += literal_len(rest);
+            spans.end = i;
+            spans.comma = true;
+        }
+        if let Some(start) = entry {
+            spans.entries.push(start..spans.end);
+        }
+        spans
+    }
+}
+
+/// How long the string, raw string or character literal at the start of `bytes` is, or 1
+/// if it starts with something else.
+fn literal_len(bytes: &[u8]) -> usize {
+    // Raw strings, such as `r#"a "quoted" word"#`, end at a quote and as many hashes
+    if bytes[0] == b'r' {
+        let hashes = bytes[1..].iter().take_while(|&&c| c == b'#').count();
+        if bytes.get(1 + hashes) == Some(&b'"') {
+            let start = 2 + hashes;
+            let end = format!("\"{}", "#".repeat(hashes));
+            return bytes[start..]
+                .windows(end.len())
+                .position(|w| w == end.as_bytes())
+                .map_or(bytes.len(), |i| start + i + end.len());
+        }
+    }
+
+    let quote = match bytes[0] {
+        b'"' | b'\'' => bytes[0],
+        _ => return 1,
+    };
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            c if c == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+pub struct LevelLoader {
+    errors: AssetErrors,
+}
+
+impl FromWorld for LevelLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            errors: world.get_resource::<AssetErrors>().unwrap().clone(),
+        }
+    }
+}
+
+pub(crate) const FILE_EXTENSIONS: &[&str] = &["levels"];
 
 impl AssetLoader for LevelLoader {
     fn load<'a>(
@@ -40,34 +250,9 @@ impl AssetLoader for LevelLoader {
         load_context: &'a mut bevy::asset::LoadContext<'_>,
     ) -> BoxedFuture<'a, Result<()>> {
         Box::pin(async move {
-            // TODO single-item file?
-            let levels: Vec<LevelFile> = ron::de::from_bytes(bytes)?;
-            levels.into_iter().for_each(|level| {
-                let LevelFile {
-                    label,
-                    recipe,
-                    bundles,
-                } = level;
-
-                let bundles = bundles
-                    .into_iter()
-                    .map(|(pos, path)| {
-                        (
-                            pos,
-                            load_context.get_handle(AssetPath::from(path.as_str()).get_id()),
-                        )
-                    })
-                    .collect();
-
-                let asset = Level {
-                    label: label.clone(),
-                    recipe,
-                    bundles,
-                };
-                load_context.set_labeled_asset(&label, LoadedAsset::new(asset));
-            });
-
-            Ok(())
+            let result = load_levels(bytes, load_context);
+            self.errors.report(load_context.path(), &result);
+            result
         })
     }
 
@@ -76,6 +261,133 @@ impl AssetLoader for LevelLoader {
     }
 }
 
+fn load_levels(bytes: &[u8], load_context: &mut LoadContext) -> Result<()> {
+    match parse_entries::<LevelFile>(bytes)? {
+        Entries::One(level) => {
+            let asset = level_asset(level, "", load_context)?;
+            load_context.set_default_asset(LoadedAsset::new(asset));
+        }
+        Entries::Many(levels) => {
+            for level in levels {
+                let label = level.label.clone();
+                let asset = level_asset(level, &label, load_context)?;
+                set_labeled_asset(load_context, &label, asset)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds a level asset, adding its inline scenery and bundles as assets labeled under
+/// `parent`.
+fn level_asset(level: LevelFile, parent: &str, load_context: &mut LoadContext) -> Result<Level> {
+    let LevelFile {
+        label,
+        recipe,
+        cauldrons,
+        scenery,
+        bundles,
+        tools,
+        par,
+    } = level;
+
+    let cauldrons = if cauldrons.is_empty() {
+        vec![CauldronFile::default()]
+    } else {
+        cauldrons
+    };
+    let cauldrons = cauldrons
+        .into_iter()
+        .enumerate()
+        .map(|(i, cauldron)| {
+            let label = inline_label(parent, &format!("cauldron{}", i), i);
+            cauldron_asset(cauldron, &label, load_context)
+        })
+        .collect::<Result<_>>()?;
+
+    let scenery = scenery
+        .into_iter()
+        .enumerate()
+        .map(|(i, (pos, scenery))| {
+            let handle = match scenery {
+                AssetRef::Path(path) => {
+                    load_context.get_handle(AssetPath::from(path.as_str()).get_id())
+                }
+                AssetRef::Inline(scenery) => {
+                    let label = match scenery.label.as_str() {
+                        "" => inline_label(parent, &format!("scenery{}", i), i),
+                        label => inline_label(parent, label, i),
+                    };
+                    let asset = scenery_asset(scenery, &label, load_context)?;
+                    set_labeled_asset(load_context, &label, asset)?
+                }
+            };
+            Ok((pos, handle))
+        })
+        .collect::<Result<_>>()?;
+
+    let bundles = bundles
+        .into_iter()
+        .enumerate()
+        .map(|(i, (pos, bundle))| {
+            let handle = match bundle {
+                AssetRef::Path(path) => {
+                    load_context.get_handle(AssetPath::from(path.as_str()).get_id())
+                }
+                AssetRef::Inline(bundle) => {
+                    let label = inline_label(parent, &bundle.label, i);
+                    let asset = bundle_asset(bundle, &label, load_context)?;
+                    set_labeled_asset(load_context, &label, asset)?
+                }
+            };
+            Ok((pos, handle))
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Level {
+        label,
+        recipe,
+        cauldrons,
+        scenery,
+        bundles,
+        tools,
+        par,
+    })
+}
+
+/// Whether every level, and the scenery, bundles, items and tools they are made of, are
+/// done loading, so that levels spawn whole. Files that failed to load count as done,
+/// since their errors are shown in game.
+pub fn levels_loaded(
+    asset_server: &AssetServer,
+    handles: &Handles,
+    levels: &Assets<Level>,
+    bundles: &Assets<ItemBundle>,
+) -> bool {
+    let done = |id: HandleId| {
+        matches!(
+            asset_server.get_load_state(id),
+            LoadState::Loaded | LoadState::Failed
+        )
+    };
+    let bundle_done = |bundle: &Handle<ItemBundle>| {
+        done(bundle.id)
+            && bundles.get(bundle).map_or(true, |bundle| {
+                bundle.items.iter().all(|(_, item)| done(item.id))
+            })
+    };
+
+    done(handles.tools.id)
+        && handles.levels.iter().all(|level| match levels.get(level) {
+            Some(level) => {
+                level.scenery.iter().all(|(_, scenery)| done(scenery.id))
+                    && level.bundles.iter().all(|(_, bundle)| bundle_done(bundle))
+            }
+            None => false,
+        })
+}
+
 pub struct SpawnLevel(Handle<Level>);
 
 impl Command for SpawnLevel {
@@ -83,13 +395,24 @@ impl Command for SpawnLevel {
         let mut command_queue = CommandQueue::default();
         let mut commands = Commands::new(&mut command_queue, world);
         let levels = world.get_resource::<Assets<Level>>().unwrap();
+        let sceneries = world.get_resource::<Assets<Scenery>>().unwrap();
 
         if levels.get(self.0.clone()).is_none() {
             eprintln!("error: could not find level '{:?}'", &self.0);
             return;
         }
 
-        for (position, bundle) in &levels.get(self.0).unwrap().bundles {
+        let level = levels.get(self.0).unwrap();
+        for (i, cauldron) in level.cauldrons.iter().enumerate() {
+            cauldron.spawn(&mut commands, i, level.recipe_of(cauldron));
+        }
+        for (position, scenery) in &level.scenery {
+            match sceneries.get(scenery) {
+                Some(scenery) => scenery.spawn(&mut commands, *position),
+                None => eprintln!("error: could not find scenery '{:?}'", scenery),
+            }
+        }
+        for (position, bundle) in &level.bundles {
             commands.spawn_item_bundle(bundle.clone(), *position);
         }
 
@@ -107,3 +430,150 @@ impl SpawnLevelExt for Commands<'_> {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVELS: &str = r##"// Brackets ( and [ in comments don't count
+[
+    (
+        label: "first",
+        /* nor ) and ] in block comments */
+        bundles: [((0., 0.), "bundles.bundles#cubes")],
+        tools: ["a \" ) ] tool"],
+    ),
+    (
+        label: "second",
+        bundles: [],
+        tools: [r#"a raw "tool" ) ]"#],
+    ),
+    // The last one
+    (
+        label: "third",
+        bundles: [],
+    )
+]
+"##;
+
+    fn level(label: &str, tools: &[&str]) -> LevelFile {
+        LevelFile {
+            label: label.to_string(),
+            recipe: Vec::new(),
+            cauldrons: Vec::new(),
+            scenery: Vec::new(),
+            bundles: Vec::new(),
+            tools: tools.iter().map(|tool| tool.to_string()).collect(),
+            par: Par::default(),
+        }
+    }
+
+    fn levels(text: &str) -> Vec<(String, Vec<String>)> {
+        parse_entries::<LevelFile>(text.as_bytes())
+            .unwrap()
+            .into_vec()
+            .into_iter()
+            .map(|level| (level.label, level.tools))
+            .collect()
+    }
+
+    #[test]
+    fn entry_spans() {
+        let spans = EntrySpans::new(LEVELS);
+        assert!(spans.list);
+        assert_eq!(spans.entries.len(), 3);
+        for (span, label) in spans.entries.iter().zip(["first", "second", "third"]) {
+            let entry = &LEVELS[span.clone()];
+            assert!(entry.starts_with('(') && entry.ends_with(')'), "{}", entry);
+            assert!(entry.contains(label), "{}", entry);
+        }
+        assert!(spans.comma);
+    }
+
+    #[test]
+    fn raw_strings() {
+        assert_eq!(literal_len(br#"r"a" )"#), 4);
+        assert_eq!(literal_len(br##"r#"a "b" )"# )"##), 12);
+        assert_eq!(literal_len(br###"r##"a "# )"## )"###), 13);
+        // Only the `r` of an identifier
+        assert_eq!(literal_len(b"recipe: []"), 1);
+    }
+
+    #[test]
+    fn replace_level() {
+        let text = splice_level(LEVELS, &level("second", &["hoof"])).unwrap();
+        assert_eq!(
+            levels(&text),
+            [
+                ("first".to_string(), vec!["a \" ) ] tool".to_string()]),
+                ("second".to_string(), vec!["hoof".to_string()]),
+                ("third".to_string(), Vec::new()),
+            ]
+        );
+        for comment in ["// Brackets", "/* nor", "// The last one"] {
+            assert!(text.contains(comment), "{}", text);
+        }
+    }
+
+    #[test]
+    fn append_level() {
+        let text = splice_level(LEVELS, &level("fourth", &[])).unwrap();
+        let labels: Vec<_> = levels(&text).into_iter().map(|(label, _)| label).collect();
+        assert_eq!(labels, ["first", "second", "third", "fourth"]);
+        let end = LEVELS.rfind(')').unwrap() + 1;
+        assert!(text.starts_with(&LEVELS[..end]), "{}", text);
+    }
+
+    #[test]
+    fn single_level_file() {
+        let text = "// Just one\n(\n    label: \"only\",\n    bundles: [],\n)\n";
+        let spliced = splice_level(text, &level("only", &["hoof"])).unwrap();
+        assert!(spliced.starts_with("// Just one\n("), "{}", spliced);
+        assert!(matches!(
+            parse_entries::<LevelFile>(spliced.as_bytes()),
+            Ok(Entries::One(level)) if level.tools == ["hoof"]
+        ));
+
+        assert!(splice_level(text, &level("other", &[])).is_err());
+    }
+
+    #[test]
+    fn empty_file() {
+        let text = splice_level("// Levels\n", &level("new", &[])).unwrap();
+        assert!(text.starts_with("// Levels\n["), "{}", text);
+        assert_eq!(levels(&text), [("new".to_string(), Vec::new())]);
+    }
+
+    fn stats(time: f32, attempts: u32, lost: u32) -> LevelStats {
+        LevelStats {
+            time,
+            attempts,
+            lost,
+        }
+    }
+
+    #[test]
+    fn stars() {
+        let par = Par {
+            time: Some(30.),
+            resets: Some(1),
+            lost: Some(0),
+        };
+        // Meeting a par exactly still counts
+        assert_eq!(par.stars(&stats(30., 2, 0)), 3);
+        assert_eq!(par.stars(&stats(31., 2, 0)), 2);
+        assert_eq!(par.stars(&stats(10., 3, 0)), 2);
+        assert_eq!(par.stars(&stats(31., 3, 0)), 1);
+        // Never fewer than one
+        assert_eq!(par.stars(&stats(31., 3, 1)), 1);
+
+        // Without a par, anything goes
+        assert_eq!(Par::default().stars(&stats(1000., 50, 10)), 3);
+        let time_only = Par {
+            time: Some(30.),
+            ..Par::default()
+        };
+        assert_eq!(time_only.stars(&stats(10., 50, 10)), 3);
+        assert_eq!(time_only.stars(&stats(40., 1, 0)), 2);
+    }
+}
@@ -1,21 +1,53 @@
-use bevy::{app::AppExit, prelude::*};
-use bevy_rapier2d::{na, prelude::*};
+use bevy::prelude::*;
+use bevy_egui::EguiPlugin;
+use bevy_rapier2d::prelude::*;
 use rand::prelude::*;
+use std::collections::HashMap;
 use wasm_bindgen::prelude::*;
 
+mod asset_errors;
+mod asset_files;
+pub mod atlas_packer;
+mod breakable;
+mod cauldron;
+pub mod check;
 mod editor;
 mod handles;
+mod input;
 mod items;
+mod joints;
 mod levels;
+mod material;
+mod menu;
+mod motion;
 mod mouse_position_world;
+mod progress;
+mod reactions;
+pub mod recipe;
+pub mod replay;
+mod scenery;
+pub mod simulation;
+mod sprite;
+mod storage;
 mod texture_atlas;
+mod tools;
 #[cfg(target_arch = "wasm32")]
 mod wasm;
 
+use asset_errors::{AssetErrors, AssetErrorsPlugin};
+use cauldron::CauldronSensor;
 use editor::EditorPlugin;
 use handles::Handles;
+use input::{Action, InputPlugin};
+use items::ItemTypes;
 use levels::{Level, SpawnLevelExt};
+use menu::MenuPlugin;
 use mouse_position_world::{MousePositionWorld, MousePositionWorldPlugin};
+use progress::{Progress, ProgressStorage};
+use reactions::{Effect, Mixture, Reactions};
+use recipe::{can_complete, CurrentRecipe, Slot};
+use replay::ReplayPlugin;
+use tools::{HeldTool, ToolControl};
 
 #[wasm_bindgen]
 pub fn run() {
@@ -25,77 +57,129 @@ pub fn run() {
     #[cfg(target_arch = "wasm32")]
     app.add_plugin(wasm::WasmPlugin);
 
+    let storage = storage::default_storage();
+    let progress = Progress::load(&*storage);
+
     app.insert_resource(WindowDescriptor {
         title: "Chevalchemy: a Hoof of Concept".to_string(),
-        width: 800.,
-        height: 600.,
+        width: WINDOW_SIZE[0],
+        height: WINDOW_SIZE[1],
         resizable: false,
         vsync: true,
         // cursor_visible: false,
         ..Default::default()
     })
     .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+    .add_plugin(LevelPlugin)
     .add_plugin(RapierRenderPlugin)
     .add_plugin(MousePositionWorldPlugin)
+    .add_plugin(InputPlugin)
+    .add_plugin(EguiPlugin)
+    .add_plugin(AssetErrorsPlugin)
     .add_plugin(EditorPlugin)
-    .add_system(bevy::input::system::exit_on_esc_system.system())
-    // Assets
-    .add_asset::<items::Item>()
-    .add_asset::<items::ItemBundle>()
-    .add_asset::<levels::Level>()
-    // Asset loaders
-    .init_asset_loader::<texture_atlas::TextureAtlasLoader>()
-    .init_asset_loader::<items::ItemLoader>()
-    .init_asset_loader::<items::ItemBundleLoader>()
-    .init_asset_loader::<levels::LevelLoader>()
-    // Events
-    .add_event::<UpdateRecipeEvent>()
-    .add_event::<ResetLevelEvent>()
-    .add_event::<NextLevelEvent>()
-    .add_event::<ItemInCauldronEvent>()
-    // Resources
-    .init_resource::<Handles>()
-    // Startup systems
-    .add_startup_system(setup.system().label("setup"))
+    .add_plugin(MenuPlugin)
+    .add_plugin(ReplayPlugin)
     .add_startup_system(setup_base.system().after("setup"))
-    // Main menu
-    .add_state(AppState::InGame)
-    .insert_resource(CurrentLevel::default())
-    .insert_resource(CurrentRecipe::default())
-    // .add_system_set(SystemSet::on_enter(AppState::MainMenu).with_system(setup_base.system()))
-    // .add_system_set_to_stage(
-    //     CoreStage::PreUpdate,
-    //     SystemSet::on_update(AppState::MainMenu).with_system(mouse_position.system()),
-    // )
-    // In-game state
-    // .add_system_set_to_stage(
-    //     CoreStage::PreUpdate,
-    //     SystemSet::on_update(AppState::InGame).with_system(mouse_position.system()),
-    // )
-    .add_system(hoof.system())
+    .insert_resource(CurrentLevel(progress.unlocked))
+    .insert_resource(progress)
+    .insert_resource(ProgressStorage(storage))
+    .add_system_set(
+        SystemSet::on_enter(AppState::LevelComplete).with_system(complete_level.system()),
+    )
+    .add_system_set(
+        SystemSet::on_update(AppState::InGame)
+            .with_system(
+                input::steer_hoof
+                    .system()
+                    .label("hoof_target")
+                    .after("mouse_position"),
+            )
+            .with_system(tools::pick_tool.system().label("pick_tool"))
+            .with_system(level_inputs.system()),
+    )
     .add_system(probe.system())
-    .add_system(cauldron_detector.system())
-    .add_system(despawn_when_oob.system())
-    .add_system(level_inputs.system())
-    .add_system(update_recipe_events.system())
-    .add_system(reset_level_events.system())
-    .add_system(next_level_events.system())
-    .add_system(item_in_cauldron_events.system())
-    // .add_system_set(
-    //     SystemSet::on_update(AppState::InGame)
-    //         .with_system(mouse_position.system())
-    //         .with_system(probe.system())
-    //         .with_system(cauldron_detector.system())
-    //         .with_system(despawn_when_oob.system())
-    //         .with_system(rules.system()),
-    // )
     .run();
 }
 
+/// The assets, state and systems that play levels, without anything that needs a window,
+/// so that the simulation plays them just like the game.
+pub(crate) struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        // The loaders report to it
+        app.init_resource::<AssetErrors>()
+            // Assets
+            .add_asset::<items::Item>()
+            .add_asset::<items::ItemBundle>()
+            .add_asset::<levels::Level>()
+            .add_asset::<reactions::Reactions>()
+            .add_asset::<scenery::Scenery>()
+            .add_asset::<tools::Tools>()
+            // Asset loaders
+            .init_asset_loader::<texture_atlas::TextureAtlasLoader>()
+            .init_asset_loader::<items::ItemLoader>()
+            .init_asset_loader::<items::ItemBundleLoader>()
+            .init_asset_loader::<levels::LevelLoader>()
+            .init_asset_loader::<reactions::ReactionsLoader>()
+            .init_asset_loader::<scenery::SceneryLoader>()
+            .init_asset_loader::<tools::ToolsLoader>()
+            // Events
+            .add_event::<UpdateRecipeEvent>()
+            .add_event::<ResetLevelEvent>()
+            .add_event::<NextLevelEvent>()
+            .add_event::<ItemInCauldronEvent>()
+            .add_event::<ItemBrokenEvent>()
+            // Resources
+            .init_resource::<Handles>()
+            .init_resource::<ItemTypes>()
+            // Startup systems
+            .add_startup_system(setup.system().label("setup"))
+            // Main menu
+            .add_state(AppState::MainMenu)
+            .init_resource::<CurrentLevel>()
+            .init_resource::<LevelStats>()
+            .init_resource::<HoofTarget>()
+            .init_resource::<ToolControl>()
+            .init_resource::<GameRng>()
+            .init_resource::<LevelFailure>()
+            // In-game state
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(enter_level.system()))
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(exit_level.system()))
+            .add_system_set(SystemSet::on_pause(AppState::InGame).with_system(pause_level.system()))
+            .add_system_set(
+                SystemSet::on_resume(AppState::InGame).with_system(resume_level.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(hoof.system().after("replay_hoof"))
+                    .with_system(tools::switch_tool.system().after("replay_hoof"))
+                    .with_system(tools::grab.system().after("replay_hoof"))
+                    .with_system(cauldron_detector.system())
+                    .with_system(motion::move_scenery.system())
+                    .with_system(level_time.system())
+                    .with_system(reload_level.system())
+                    .with_system(reset_level_events.system())
+                    .with_system(next_level_events.system())
+                    .with_system(item_in_cauldron_events.system())
+                    .with_system(breakable::break_items.system().label("break_items"))
+                    .with_system(item_broken_events.system().before("break_items")),
+            )
+            .add_system(despawn_when_oob.system())
+            .add_system(items::update_item_types.system())
+            .add_system(update_recipe_events.system());
+    }
+}
+
+const WINDOW_SIZE: [f32; 2] = [800., 600.];
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 enum AppState {
     MainMenu,
     InGame,
+    LevelComplete,
+    LevelFailed,
+    GameEnd,
 }
 
 struct CurrentLevel(usize);
@@ -106,186 +190,154 @@ impl Default for CurrentLevel {
     }
 }
 
-#[derive(Clone, Default)]
-struct CurrentRecipe {
-    items: Vec<ItemType>,
-    next_index: usize,
+type ItemType = String;
+
+/// How the current level is going, across all attempts.
+#[derive(Default)]
+struct LevelStats {
+    time: f32,
+    attempts: u32,
+    lost: u32,
 }
 
-impl CurrentRecipe {
-    fn new(items: Vec<ItemType>) -> Self {
-        Self {
-            items,
-            next_index: 0,
-        }
+impl LevelStats {
+    fn resets(&self) -> u32 {
+        self.attempts.saturating_sub(1)
     }
+}
 
-    fn has_next_item(&self) -> bool {
-        self.next_index < self.items.len()
-    }
+/// Why the current level was failed, for the failure screen.
+#[derive(Default)]
+struct LevelFailure {
+    expected: Vec<ItemType>,
+    cause: FailureCause,
+}
 
-    fn next_item(&self) -> Option<&ItemType> {
-        self.items.get(self.next_index)
-    }
+enum FailureCause {
+    Dropped(ItemType),
+    Broken(ItemType),
+}
 
-    fn check_item(&mut self) {
-        self.next_index += 1;
+impl Default for FailureCause {
+    fn default() -> Self {
+        Self::Dropped(ItemType::default())
     }
 }
 
-type ItemType = String;
+#[derive(Default)]
+struct HoofTarget(Vec2);
+
+/// How fast a tool can follow the hoof target.
+#[derive(Clone, Copy)]
+struct HoofSettings {
+    /// Units per second. Tools use CCD, so that they don't pass through items at any
+    /// speed.
+    max_speed: f32,
+    /// Units per second squared
+    max_acceleration: f32,
+}
 
-fn item_type_to_atlas_index(item_type: &str) -> u32 {
-    match item_type {
-        "eyed_vial" => 0,
-        "support" => 1,
-        "radioactive_vial" => 3,
-        "bone" => 5,
-        "mug" => 7,
-        "yorick" => 8,
-        "vial_stand" => 9,
-        "red_vial" => 11,
-        "yellow_vial" => 12,
-        "blue_vial" => 13,
-        "cube" => 14,
-        "gold_nugget" => 20,
-        _ => panic!("unknown item type '{}'", item_type),
+impl Default for HoofSettings {
+    fn default() -> Self {
+        Self {
+            max_speed: 1000.,
+            max_acceleration: 20000.,
+        }
     }
 }
 
-struct UpdateRecipeEvent;
-struct ResetLevelEvent;
-struct NextLevelEvent;
-struct ItemInCauldronEvent(ItemType);
-
-struct IsItem(ItemType);
-struct Mouse;
-struct MainCamera;
-struct CauldronSensor;
-struct RecipeDisplay;
-
-fn spawn_cuboid(commands: &mut Commands, pos: Vec2, size: Vec2) {
-    commands
-        .spawn_bundle(RigidBodyBundle {
-            body_type: RigidBodyType::Static,
-            position: RigidBodyPosition {
-                position: na::Isometry2::translation(pos.x, pos.y),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert_bundle(ColliderBundle {
-            shape: ColliderShape::cuboid(size.x / 2., size.y / 2.),
-            ..Default::default()
-        })
-        .insert(RigidBodyPositionSync::Discrete);
+impl HoofSettings {
+    /// The velocity that takes the hoof from `position` towards `target` over the next
+    /// `dt` seconds, given how fast it is already going.
+    fn velocity(&self, position: Vec2, velocity: Vec2, target: Vec2, dt: f32) -> Vec2 {
+        let to_target = target - position;
+        // No faster than it can brake, so that it stops at the target
+        let braking = (2. * self.max_acceleration * to_target.length()).sqrt();
+        let wanted = clamp_length(to_target / dt, self.max_speed.min(braking));
+        velocity + clamp_length(wanted - velocity, self.max_acceleration * dt)
+    }
 }
 
-fn spawn_cupboard(commands: &mut Commands) {
-    let board_size = Vec2::new(125., 5.);
+fn clamp_length(v: Vec2, max: f32) -> Vec2 {
+    let length = v.length();
+    if length > max {
+        v * (max / length)
+    } else {
+        v
+    }
+}
 
-    spawn_cuboid(commands, Vec2::new(-125., 87.), board_size);
-    spawn_cuboid(commands, Vec2::new(-125., 25.), board_size);
-    spawn_cuboid(commands, Vec2::new(-125., -37.), board_size);
+struct GameRng(StdRng);
 
-    spawn_cuboid(commands, Vec2::new(125., 87.), board_size);
-    spawn_cuboid(commands, Vec2::new(125., 25.), board_size);
-    spawn_cuboid(commands, Vec2::new(125., -37.), board_size);
+impl Default for GameRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
 }
 
-fn is_clockwise(vertices: &[[f32; 2]]) -> bool {
-    (vertices
-        .iter()
-        .zip(vertices.iter().cycle().skip(1))
-        .map(|([x0, y0], [x1, y1])| x0 * y1 - x1 * y0)
-        .sum::<f32>())
-        < 0.0
+impl GameRng {
+    fn reseed(&mut self, seed: u64) {
+        self.0 = StdRng::seed_from_u64(seed);
+    }
 }
 
-fn make_convex_poly(shape: &[[f32; 2]]) -> ColliderShape {
-    let shape = if is_clockwise(shape) {
-        shape
-            .iter()
-            .rev()
-            .cloned()
-            .map(Into::into)
-            .collect::<Vec<_>>()
-    } else {
-        shape.iter().cloned().map(Into::into).collect::<Vec<_>>()
-    };
-    ColliderShape::convex_polyline(shape).unwrap()
+struct UpdateRecipeEvent;
+struct ResetLevelEvent {
+    /// Respawned because its files changed, which doesn't count as an attempt.
+    reloaded: bool,
 }
-
-fn make_compound_shape(shapes: &[Vec<[f32; 2]>]) -> ColliderShape {
-    ColliderShape::compound(
-        shapes
-            .iter()
-            .map(|shape| ([0., 0.].into(), make_convex_poly(shape)))
-            .collect(),
-    )
+struct NextLevelEvent;
+struct ItemInCauldronEvent {
+    cauldron: usize,
+    ty: ItemType,
 }
+struct ItemBrokenEvent(ItemType);
 
-fn cauldron(commands: &mut Commands, handles: &Res<Handles>) {
-    let shapes = vec![
-        vec![[-53.5, 40.0], [-49.5, 38.0], [-58.5, 0.0], [-60.5, 8.0]],
-        vec![[-60.5, 7.5], [-42.0, -15.0], [-54.5, -9.5], [-60.0, -1.0]],
-        vec![[-46.0, -12.5], [48.5, -12.5], [26.0, -18.5], [-22.0, -18.0]],
-        vec![[48.5, -14.0], [55.5, -9.0], [62.0, 3.0], [60.5, 16.0]],
-        vec![[61.0, 12.0], [52.0, 40.0], [51.0, 35.0], [60.0, 3.0]],
-    ];
-    let sensor = vec![
-        // [-54.5, -84.5],
-        // [59.5, -83.5],
-        // [46.5, -100.5],
-        // [-44.0, -99.5],
-        [-55.5, 4.5],
-        [58.5, 5.5],
-        [45.5, -11.5],
-        [-45.0, -10.5],
-    ];
-
-    // for shape in &shapes {
-    //     eprintln!("vec![");
-    // for [x, y] in &sensor {
-    //     eprintln!("[{:.0}.0, {:.0}.0],", x - 1., y + 150. - 61.);
-    // }
-    //     eprintln!("],");
-    // }
+struct IsItem(ItemType);
+struct MainCamera;
+struct RecipeDisplay;
+/// Despawned with the level, like its items.
+struct LevelEntity;
+
+fn smoke(
+    commands: &mut Commands,
+    handles: &Res<Handles>,
+    rng: &mut StdRng,
+    at: Vec2,
+    color: Color,
+) {
+    particles(commands, handles, rng, at, color, 10, 1.);
+}
 
-    commands
-        .spawn_bundle(SpriteBundle {
-            transform: Transform::from_xyz(0., 0., 100.),
-            material: handles.cauldron_material.clone(),
-            ..Default::default()
-        })
-        .insert_bundle(RigidBodyBundle {
-            body_type: RigidBodyType::Static,
-            position: [1., -150. + 61.].into(),
-            ..Default::default()
-        })
-        .insert_bundle(ColliderBundle {
-            shape: make_compound_shape(&shapes),
-            ..Default::default()
-        })
-        .insert(RigidBodyPositionSync::Discrete)
-        .with_children(|parent| {
-            parent
-                .spawn_bundle(ColliderBundle {
-                    collider_type: ColliderType::Sensor,
-                    shape: make_convex_poly(&sensor),
-                    ..Default::default()
-                })
-                .insert(CauldronSensor);
-        });
+/// Small puffs that rise slowly out of the cauldron.
+fn bubbles(
+    commands: &mut Commands,
+    handles: &Res<Handles>,
+    rng: &mut StdRng,
+    at: Vec2,
+    color: Color,
+) {
+    particles(commands, handles, rng, at, color, 6, 0.5);
 }
 
-fn smoke(commands: &mut Commands, handles: &Res<Handles>, color: Color) {
-    let mut rng = rand::thread_rng();
+/// A big cloud, for reactions.
+fn puff(commands: &mut Commands, handles: &Res<Handles>, rng: &mut StdRng, at: Vec2, color: Color) {
+    particles(commands, handles, rng, at, color, 25, 1.5);
+}
 
-    for _ in 0..10 {
+fn particles(
+    commands: &mut Commands,
+    handles: &Res<Handles>,
+    rng: &mut StdRng,
+    at: Vec2,
+    color: Color,
+    count: usize,
+    size: f32,
+) {
+    for _ in 0..count {
         let index = rng.gen_range(0..4);
-        let pos = Vec2::new(rng.gen_range(-50.0..50.0), rng.gen_range(-62.5..-37.5));
-        let speed = Vec2::new(rng.gen_range(-50.0..50.0), rng.gen_range(25.0..50.0));
+        let pos = at + Vec2::new(rng.gen_range(-50.0..50.0), rng.gen_range(26.5..51.5));
+        let speed = Vec2::new(rng.gen_range(-50.0..50.0), rng.gen_range(25.0..50.0)) * size;
 
         commands
             .spawn_bundle(SpriteSheetBundle {
@@ -295,7 +347,8 @@ fn smoke(commands: &mut Commands, handles: &Res<Handles>, color: Color) {
                     ..TextureAtlasSprite::default()
                 },
                 texture_atlas: handles.smoke_atlas.clone(),
-                transform: Transform::from_xyz(0., 0., 5.),
+                transform: Transform::from_xyz(0., 0., 5.)
+                    * Transform::from_scale(Vec3::splat(size)),
                 ..Default::default()
             })
             .insert_bundle(RigidBodyBundle {
@@ -315,14 +368,12 @@ fn smoke(commands: &mut Commands, handles: &Res<Handles>, color: Color) {
     }
 }
 
-fn setup(mut commands: Commands, mut reset_level_events: EventWriter<ResetLevelEvent>) {
+fn setup(mut commands: Commands) {
     commands.insert_resource(RapierConfiguration {
         gravity: Vec2::new(0., -98.1 * 2.).into(),
         scale: 1.0,
         ..Default::default()
     });
-
-    reset_level_events.send(ResetLevelEvent);
 }
 
 fn setup_base(mut commands: Commands, handles: Res<Handles>) {
@@ -356,53 +407,6 @@ fn setup_base(mut commands: Commands, handles: Res<Handles>) {
         transform: Transform::from_xyz(167.5, -117.5, 0.),
         ..Default::default()
     });
-
-    // Cauldron
-    cauldron(&mut commands, &handles);
-
-    // Cupboard colliders
-    spawn_cupboard(&mut commands);
-
-    // Mouse
-    let hoof_shape = vec![
-        [4.5, 17.0],
-        [-16.0, -4.5],
-        [-6.0, -16.5],
-        [17.0, -7.5],
-        [10.5, 12.0],
-    ];
-    commands
-        .spawn_bundle(SpriteBundle {
-            ..Default::default()
-        })
-        .insert_bundle(RigidBodyBundle {
-            body_type: RigidBodyType::KinematicPositionBased,
-            // ccd: RigidBodyCcd {
-            //     ccd_enabled: true,
-            //     ..Default::default()
-            // },
-            ..Default::default()
-        })
-        .insert_bundle(ColliderBundle {
-            shape: ColliderShape::convex_polyline(
-                hoof_shape
-                    .iter()
-                    .cloned()
-                    .map(Into::into)
-                    .collect::<Vec<_>>(),
-            )
-            .unwrap(),
-            ..Default::default()
-        })
-        .insert(RigidBodyPositionSync::Discrete)
-        .insert(Mouse)
-        .with_children(|parent| {
-            parent.spawn_bundle(SpriteBundle {
-                transform: Transform::from_xyz(200. - 19., -162. + 19., 500.),
-                material: handles.leg_material.clone(),
-                ..Default::default()
-            });
-        });
 }
 
 fn start_level(commands: &mut Commands, handles: &Res<Handles>) {
@@ -450,12 +454,86 @@ fn start_level(commands: &mut Commands, handles: &Res<Handles>) {
         });
 }
 
+fn enter_level(
+    mut reset_level_events: EventWriter<ResetLevelEvent>,
+    mut stats: ResMut<LevelStats>,
+) {
+    *stats = LevelStats::default();
+    reset_level_events.send(ResetLevelEvent { reloaded: false });
+}
+
+fn exit_level(
+    mut commands: Commands,
+    items: Query<Entity, With<IsItem>>,
+    level_entities: Query<Entity, With<LevelEntity>>,
+    recipe_display: Query<Entity, With<RecipeDisplay>>,
+) {
+    items
+        .iter()
+        .chain(level_entities.iter())
+        .chain(recipe_display.iter())
+        .for_each(|e| commands.entity(e).despawn_recursive());
+}
+
+fn pause_level(mut rapier_configuration: ResMut<RapierConfiguration>) {
+    rapier_configuration.physics_pipeline_active = false;
+}
+
+fn resume_level(mut rapier_configuration: ResMut<RapierConfiguration>) {
+    rapier_configuration.physics_pipeline_active = true;
+}
+
+fn level_time(time: Res<Time>, mut stats: ResMut<LevelStats>) {
+    stats.time += time.delta_seconds();
+}
+
+fn complete_level(
+    mut progress: ResMut<Progress>,
+    storage: Res<ProgressStorage>,
+    stats: Res<LevelStats>,
+    current_level: Res<CurrentLevel>,
+    handles: Res<Handles>,
+    levels: Res<Assets<Level>>,
+) {
+    if let Some(level) = levels.get(&handles.levels[current_level.0]) {
+        progress.complete(&level.label, stats.time, level.par.stars(&stats));
+    }
+    if current_level.0 + 1 < handles.levels.len() {
+        progress.unlock(current_level.0 + 1);
+    }
+    if let Err(e) = progress.save(&*storage.0) {
+        eprintln!("error: could not save progress: {:#}", e);
+    }
+}
+
+/// Steers the tool towards the hoof target with a real velocity, so that it hits items
+/// as hard as it moves.
 fn hoof(
-    mouse_position_world: Res<MousePositionWorld>,
-    mut hoof: Query<&mut RigidBodyPosition, With<Mouse>>,
+    integration_parameters: Res<IntegrationParameters>,
+    hoof_target: Res<HoofTarget>,
+    mut hoof: Query<(&HeldTool, &RigidBodyPosition, &mut RigidBodyVelocity)>,
 ) {
-    let mut hoof = hoof.single_mut().unwrap();
-    hoof.next_position.translation.vector = mouse_position_world.0.into();
+    // The velocity is kept for the whole physics step, as in the simulation
+    let dt = integration_parameters.dt;
+    if dt <= 0. {
+        return;
+    }
+    // There is none until the tools are loaded
+    let (tool, position, mut velocity) = match hoof.single_mut() {
+        Ok(hoof) => hoof,
+        Err(_) => return,
+    };
+    let position = position.position.translation;
+    let linvel = velocity.linvel;
+    velocity.linvel = tool
+        .settings
+        .velocity(
+            Vec2::new(position.x, position.y),
+            Vec2::new(linvel.x, linvel.y),
+            hoof_target.0,
+            dt,
+        )
+        .into();
 }
 
 fn probe(
@@ -484,36 +562,58 @@ fn probe(
 
 fn cauldron_detector(
     mut commands: Commands,
-    cauldron: Query<Entity, With<CauldronSensor>>,
+    cauldrons: Query<(Entity, &CauldronSensor)>,
     items: Query<&IsItem>,
+    parents: Query<&Parent>,
     narrow_phase: Res<NarrowPhase>,
     mut item_in_cauldron_events: EventWriter<ItemInCauldronEvent>,
 ) {
-    let cauldron = cauldron.single().unwrap();
-    let handle = cauldron.handle();
-    for (h1, h2, _b) in narrow_phase.intersections_with(handle) {
-        let other = if h1 == handle { h2 } else { h1 };
-        let other = other.entity();
-        if let Ok(item) = items.get(other) {
-            commands.entity(other).despawn_recursive();
-
-            item_in_cauldron_events.send(ItemInCauldronEvent(item.0.clone()));
+    let mut found = Vec::new();
+    for (cauldron, sensor) in cauldrons.iter() {
+        let handle = cauldron.handle();
+        for (h1, h2, _b) in narrow_phase.intersections_with(handle) {
+            let other = if h1 == handle { h2 } else { h1 };
+            // Colliders with their own material are children of the item
+            let other = other.entity();
+            let other = parents.get(other).map_or(other, |parent| parent.0);
+            if found.contains(&other) {
+                continue;
+            }
+            if let Ok(item) = items.get(other) {
+                found.push(other);
+                commands.entity(other).despawn_recursive();
+
+                item_in_cauldron_events.send(ItemInCauldronEvent {
+                    cauldron: sensor.index,
+                    ty: item.0.clone(),
+                });
+            }
         }
     }
 }
 
+/// How far things can go past the window before they are lost.
+const LOST_MARGIN: f32 = 1000.;
+
+fn is_lost(position: Vec2) -> bool {
+    position.x < -LOST_MARGIN
+        || position.y < -LOST_MARGIN
+        || position.x > WINDOW_SIZE[0] + LOST_MARGIN
+        || position.y > WINDOW_SIZE[1] + LOST_MARGIN
+}
+
 fn despawn_when_oob(
     mut commands: Commands,
-    wnds: Res<Windows>,
-    query: Query<(Entity, &Transform)>,
+    mut stats: ResMut<LevelStats>,
+    // Only bodies, since other things such as sprites on bodies have local transforms
+    query: Query<(Entity, &Transform, Option<&IsItem>), With<RigidBodyPosition>>,
 ) {
-    let wnd = wnds.get_primary().unwrap();
-    let size = Vec2::new(wnd.width() as f32, wnd.height() as f32);
-
-    for (entity, transform) in query.iter() {
-        let pos = &transform.translation;
-        if pos.x < -1000. || pos.y < -1000. || pos.x > size.x + 1000. || pos.y > size.y + 1000. {
-            commands.entity(entity).despawn();
+    for (entity, transform, is_item) in query.iter() {
+        if is_lost(transform.translation.truncate()) {
+            if is_item.is_some() {
+                stats.lost += 1;
+            }
+            commands.entity(entity).despawn_recursive();
         }
     }
 }
@@ -521,20 +621,28 @@ fn despawn_when_oob(
 fn update_recipe_events(
     mut update_recipe_events: EventReader<UpdateRecipeEvent>,
     mut commands: Commands,
-    current_recipe: Res<CurrentRecipe>,
+    cauldrons: Query<(&CauldronSensor, &CurrentRecipe)>,
+    spawned: Query<Entity, Added<CurrentRecipe>>,
+    item_types: Res<ItemTypes>,
     handles: Res<Handles>,
     recipe_display: Query<Entity, With<RecipeDisplay>>,
 ) {
-    if let Some(_) = update_recipe_events.iter().last() {
-        // Remove previous display, if any
-        for entity in recipe_display.iter() {
-            commands.entity(entity).despawn_recursive();
-        }
-        // Make a new one
+    // Cauldrons are spawned by a command, so they may show up after the event
+    let updated = update_recipe_events.iter().last().is_some();
+    if !updated && spawned.iter().next().is_none() {
+        return;
+    }
+
+    // Remove previous display, if any
+    for entity in recipe_display.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // Make a new one above each cauldron
+    for (sensor, current_recipe) in cauldrons.iter() {
         commands
             .spawn()
             .insert_bundle(SpriteBundle {
-                transform: Transform::from_xyz(0., 150., 1.)
+                transform: Transform::from_xyz(sensor.position.x, 150., 1.)
                     * Transform::from_scale(Vec3::splat(0.75)),
                 ..Default::default()
             })
@@ -542,95 +650,290 @@ fn update_recipe_events(
             .with_children(|parent| {
                 let mut x = -35.;
                 let mut y = -60.;
-                for (i, item) in current_recipe.items.iter().enumerate() {
-                    let index = item_type_to_atlas_index(&item);
-                    parent.spawn_bundle(SpriteSheetBundle {
-                        sprite: TextureAtlasSprite::new(index),
-                        texture_atlas: handles.items_atlas.clone(),
-                        transform: Transform::from_xyz(x, y, 0.),
-                        ..Default::default()
-                    });
-
-                    if i < current_recipe.next_index {
-                        parent.spawn_bundle(SpriteSheetBundle {
-                            sprite: TextureAtlasSprite::new(2),
-                            texture_atlas: handles.controls_atlas.clone(),
-                            transform: Transform::from_xyz(x, y, 0.),
-                            ..Default::default()
-                        });
+                for step in current_recipe.steps() {
+                    if step.len() == 1 {
+                        let position = Vec2::new(x, y);
+                        spawn_recipe_slot(parent, &step[0], position, &item_types, &handles);
+                        x = -x;
+                    } else {
+                        // Slots that can be filled in any order share a row
+                        for (i, slot) in step.iter().enumerate() {
+                            let offset = i as f32 - (step.len() - 1) as f32 / 2.;
+                            let position = Vec2::new(offset * 30., y);
+                            spawn_recipe_slot(parent, slot, position, &item_types, &handles);
+                        }
                     }
-
-                    x = -x;
                     y -= 30.;
                 }
             });
     }
 }
 
+fn spawn_recipe_slot(
+    parent: &mut ChildBuilder,
+    slot: &Slot,
+    position: Vec2,
+    item_types: &ItemTypes,
+    handles: &Handles,
+) {
+    // Alternatives are shown side by side, and smaller
+    let scale = if slot.types.len() > 1 { 0.6 } else { 1. };
+    for (i, ty) in slot.types.iter().enumerate() {
+        let offset = (i as f32 - (slot.types.len() - 1) as f32 / 2.) * 14.;
+        let transform = Transform::from_xyz(position.x + offset, position.y, 0.)
+            * Transform::from_scale(Vec3::splat(scale));
+        if let Some(info) = item_types.get(ty) {
+            parent.spawn_bundle(SpriteSheetBundle {
+                sprite: TextureAtlasSprite::new(info.texture_index),
+                texture_atlas: info.texture_atlas.clone(),
+                transform,
+                ..Default::default()
+            });
+        } else {
+            eprintln!("error: unknown item type '{}'", ty);
+            parent.spawn_bundle(SpriteBundle {
+                sprite: Sprite::new(Vec2::splat(24.)),
+                material: handles.placeholder_material.clone(),
+                transform,
+                ..Default::default()
+            });
+        }
+    }
+
+    if slot.filled {
+        parent.spawn_bundle(SpriteSheetBundle {
+            sprite: TextureAtlasSprite::new(2),
+            texture_atlas: handles.controls_atlas.clone(),
+            transform: Transform::from_xyz(position.x, position.y, 0.),
+            ..Default::default()
+        });
+    }
+}
+
 fn item_in_cauldron_events(
     mut item_in_cauldron_events: EventReader<ItemInCauldronEvent>,
-    mut next_level_events: EventWriter<NextLevelEvent>,
     mut update_recipe_events: EventWriter<UpdateRecipeEvent>,
-    mut reset_level_events: EventWriter<ResetLevelEvent>,
     mut commands: Commands,
-    mut current_recipe: ResMut<CurrentRecipe>,
+    mut rng: ResMut<GameRng>,
+    mut state: ResMut<State<AppState>>,
+    mut cauldrons: Query<(&CauldronSensor, &mut CurrentRecipe, &mut Mixture)>,
     handles: Res<Handles>,
+    reactions: Res<Assets<Reactions>>,
+    items: Res<Assets<items::Item>>,
 ) {
-    for ItemInCauldronEvent(item_type) in item_in_cauldron_events.iter() {
-        if let Some(next_item_type) = current_recipe.next_item() {
-            // If it's the correct item
-            if next_item_type == item_type {
-                current_recipe.check_item();
-                update_recipe_events.send(UpdateRecipeEvent);
-                smoke(&mut commands, &handles, Color::DARK_GREEN);
-                if !current_recipe.has_next_item() {
-                    // TODO success screen
-                    next_level_events.send(NextLevelEvent);
+    for ItemInCauldronEvent { cauldron, ty } in item_in_cauldron_events.iter() {
+        if cauldrons
+            .iter_mut()
+            .all(|(_, recipe, _)| recipe.is_complete())
+        {
+            break;
+        }
+        let (sensor, mut current_recipe, mut mixture) = match cauldrons
+            .iter_mut()
+            .find(|(sensor, _, _)| sensor.index == *cauldron)
+        {
+            Some(found) => found,
+            None => {
+                eprintln!("error: could not find cauldron {}", cauldron);
+                continue;
+            }
+        };
+        let at = sensor.position;
+
+        mixture.history.push(ty.clone());
+        let mut forgiven = false;
+        let reactions: Vec<_> = reactions
+            .get(&handles.reactions)
+            .into_iter()
+            .flat_map(|reactions| &reactions.0)
+            .filter(|reaction| reaction.matches(&mixture.history))
+            .collect();
+        for reaction in reactions {
+            for effect in &reaction.effects {
+                let color = mixture.color.unwrap_or(Color::WHITE);
+                match effect {
+                    Effect::Color(color) => mixture.color = Some(*color),
+                    Effect::Bubble => bubbles(&mut commands, &handles, &mut rng.0, at, color),
+                    Effect::Puff => puff(&mut commands, &handles, &mut rng.0, at, color),
+                    Effect::Spawn(item, offset) => match items.get(item) {
+                        Some(item) => item.spawn(&mut commands.spawn(), at + *offset),
+                        None => eprintln!("error: could not find item '{:?}'", item),
+                    },
+                    Effect::Forgive => forgiven = true,
                 }
-            } else {
-                // Otherwise
-                // TODO failure
-                smoke(&mut commands, &handles, Color::CRIMSON);
-                reset_level_events.send(ResetLevelEvent);
             }
         }
+
+        let expected = current_recipe.expected().into_iter().cloned().collect();
+        // If it's the correct item
+        if current_recipe.add(ty) {
+            update_recipe_events.send(UpdateRecipeEvent);
+            let color = mixture.color.unwrap_or(Color::DARK_GREEN);
+            smoke(&mut commands, &handles, &mut rng.0, at, color);
+            if cauldrons
+                .iter_mut()
+                .all(|(_, recipe, _)| recipe.is_complete())
+            {
+                // Unless a broken item already failed the level this frame
+                state.push(AppState::LevelComplete).ok();
+            }
+        } else if forgiven {
+            smoke(&mut commands, &handles, &mut rng.0, at, Color::CRIMSON);
+        } else {
+            // Otherwise
+            smoke(&mut commands, &handles, &mut rng.0, at, Color::CRIMSON);
+            // The first failure is the one shown, as in `item_broken_events`
+            if state.push(AppState::LevelFailed).is_ok() {
+                commands.insert_resource(LevelFailure {
+                    expected,
+                    cause: FailureCause::Dropped(ty.clone()),
+                });
+            }
+            break;
+        }
+    }
+}
+
+/// Fails the level when an item it still needs breaks and there are not enough left.
+///
+/// Runs before `break_items`, so that the fragments from last frame have been spawned.
+fn item_broken_events(
+    mut item_broken_events: EventReader<ItemBrokenEvent>,
+    mut commands: Commands,
+    mut state: ResMut<State<AppState>>,
+    recipes: Query<&CurrentRecipe>,
+    items: Query<&IsItem>,
+) {
+    let broken = match item_broken_events.iter().last() {
+        Some(ItemBrokenEvent(item_type)) => item_type,
+        None => return,
+    };
+    if recipes.iter().all(CurrentRecipe::is_complete) {
+        return;
+    }
+
+    let mut available: HashMap<&str, usize> = HashMap::new();
+    for IsItem(item_type) in items.iter() {
+        *available.entry(item_type).or_default() += 1;
+    }
+    if !can_complete(recipes.iter(), &available) && state.push(AppState::LevelFailed).is_ok() {
+        let mut expected = Vec::new();
+        for ty in recipes.iter().flat_map(CurrentRecipe::expected) {
+            if !expected.contains(ty) {
+                expected.push(ty.clone());
+            }
+        }
+        commands.insert_resource(LevelFailure {
+            expected,
+            cause: FailureCause::Broken(broken.clone()),
+        });
     }
 }
 
 fn reset_level_events(
     mut reset_level_events: EventReader<ResetLevelEvent>,
     current_level: Res<CurrentLevel>,
-    mut current_recipe: ResMut<CurrentRecipe>,
     mut update_recipe_events: EventWriter<UpdateRecipeEvent>,
+    mut stats: ResMut<LevelStats>,
 
     mut commands: Commands,
     levels: Res<Assets<Level>>,
     handles: Res<Handles>,
     items: Query<Entity, With<IsItem>>,
+    level_entities: Query<Entity, With<LevelEntity>>,
 ) {
-    if let Some(_) = reset_level_events.iter().last() {
+    let mut reset = false;
+    let mut attempt = false;
+    for event in reset_level_events.iter() {
+        reset = true;
+        attempt |= !event.reloaded;
+    }
+    if reset {
         let level_handle = handles.levels[current_level.0].clone();
-        let level = levels.get(level_handle.clone_weak()).unwrap();
-        *current_recipe = CurrentRecipe::new(level.recipe.clone());
-        items.for_each(|e| commands.entity(e).despawn_recursive());
+        if levels.get(level_handle.clone_weak()).is_none() {
+            eprintln!("error: could not find level '{:?}'", level_handle);
+            return;
+        }
+        if attempt {
+            stats.attempts += 1;
+        }
+        items
+            .iter()
+            .chain(level_entities.iter())
+            .for_each(|e| commands.entity(e).despawn_recursive());
         commands.spawn_level(level_handle);
         update_recipe_events.send(UpdateRecipeEvent);
     }
 }
 
+/// Restarts the current level when it, or any of its scenery, bundles or items, is
+/// reloaded.
+fn reload_level(
+    mut level_events: EventReader<AssetEvent<Level>>,
+    mut scenery_events: EventReader<AssetEvent<scenery::Scenery>>,
+    mut bundle_events: EventReader<AssetEvent<items::ItemBundle>>,
+    mut item_events: EventReader<AssetEvent<items::Item>>,
+    mut reset_level_events: EventWriter<ResetLevelEvent>,
+    current_level: Res<CurrentLevel>,
+    handles: Res<Handles>,
+    levels: Res<Assets<Level>>,
+    bundles: Res<Assets<items::ItemBundle>>,
+) {
+    fn modified<T: bevy::asset::Asset>(event: &AssetEvent<T>) -> Option<&Handle<T>> {
+        match event {
+            AssetEvent::Modified { handle } => Some(handle),
+            _ => None,
+        }
+    }
+
+    let level_handle = &handles.levels[current_level.0];
+    let level = match levels.get(level_handle) {
+        Some(level) => level,
+        None => return,
+    };
+    let level_bundles = || level.bundles.iter().map(|(_, bundle)| bundle);
+
+    let level_changed = level_events
+        .iter()
+        .filter_map(modified)
+        .any(|handle| handle == level_handle);
+    let scenery_changed = scenery_events
+        .iter()
+        .filter_map(modified)
+        .any(|handle| level.scenery.iter().any(|(_, scenery)| scenery == handle));
+    let bundle_changed = bundle_events
+        .iter()
+        .filter_map(modified)
+        .any(|handle| level_bundles().any(|bundle| bundle == handle));
+    let item_changed = item_events.iter().filter_map(modified).any(|handle| {
+        level_bundles()
+            .filter_map(|bundle| bundles.get(bundle))
+            .flat_map(|bundle| bundle.items.iter())
+            .any(|(_, item)| item == handle)
+    });
+
+    if level_changed || scenery_changed || bundle_changed || item_changed {
+        reset_level_events.send(ResetLevelEvent { reloaded: true });
+    }
+}
+
 fn next_level_events(
     mut next_level_events: EventReader<NextLevelEvent>,
     mut reset_level_events: EventWriter<ResetLevelEvent>,
     mut current_level: ResMut<CurrentLevel>,
+    mut stats: ResMut<LevelStats>,
+    mut state: ResMut<State<AppState>>,
     handles: Res<Handles>,
 ) {
     if let Some(_) = next_level_events.iter().last() {
         if current_level.0 < handles.levels.len() - 1 {
             current_level.0 += 1;
+            *stats = LevelStats::default();
             // Reset level if there's a next one
-            reset_level_events.send(ResetLevelEvent);
+            reset_level_events.send(ResetLevelEvent { reloaded: false });
         } else {
-            // TODO game end!
+            // Unless another transition was queued this frame
+            state.push(AppState::GameEnd).ok();
         }
     }
 }
@@ -638,17 +941,21 @@ fn next_level_events(
 fn level_inputs(
     mouse_position_world: Res<MousePositionWorld>,
     buttons: Res<Input<MouseButton>>,
+    actions: Res<Input<Action>>,
     mut reset_level_events: EventWriter<ResetLevelEvent>,
-    mut exit: EventWriter<AppExit>,
+    mut state: ResMut<State<AppState>>,
 ) {
-    if buttons.just_released(MouseButton::Left) {
-        // Exit
-        if (mouse_position_world.0 - Vec2::new(-167.5, -117.5)).length() < 22.5 {
-            exit.send(AppExit);
-        }
-        // Restart
-        if (mouse_position_world.0 - Vec2::new(167.5, -117.5)).length() < 22.5 {
-            reset_level_events.send(ResetLevelEvent);
-        }
+    let clicked = |position: Vec2| {
+        buttons.just_released(MouseButton::Left)
+            && (mouse_position_world.0 - position).length() < 22.5
+    };
+    // Exit
+    if clicked(Vec2::new(-167.5, -117.5)) || actions.just_pressed(Action::Back) {
+        // Unless the level was completed or failed this frame
+        state.set(AppState::MainMenu).ok();
+    }
+    // Restart
+    if clicked(Vec2::new(167.5, -117.5)) || actions.just_pressed(Action::Restart) {
+        reset_level_events.send(ResetLevelEvent { reloaded: false });
     }
 }
@@ -0,0 +1,88 @@
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How an item or one of its colliders behaves physically. Anything left out uses
+/// Rapier's defaults, or for a collider, the item's own material.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub(crate) struct Material {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub density: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub friction: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restitution: Option<f32>,
+    // These apply to the whole body, so only to items
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linear_damping: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub angular_damping: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub can_sleep: Option<bool>,
+}
+
+impl Material {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// This material, with anything it leaves out taken from `fallback`.
+    pub fn or(&self, fallback: &Material) -> Material {
+        Material {
+            density: self.density.or(fallback.density),
+            friction: self.friction.or(fallback.friction),
+            restitution: self.restitution.or(fallback.restitution),
+            linear_damping: self.linear_damping.or(fallback.linear_damping),
+            angular_damping: self.angular_damping.or(fallback.angular_damping),
+            can_sleep: self.can_sleep.or(fallback.can_sleep),
+        }
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        match (self.density, self.friction, self.restitution) {
+            (Some(density), _, _) if density <= 0. => {
+                Err(format!("density {} is not positive", density))
+            }
+            (_, Some(friction), _) if friction < 0. => {
+                Err(format!("friction {} is negative", friction))
+            }
+            (_, _, Some(restitution)) if restitution < 0. => {
+                Err(format!("restitution {} is negative", restitution))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether this sets anything that only makes sense for a whole item.
+    pub fn has_body_settings(&self) -> bool {
+        self.linear_damping.is_some() || self.angular_damping.is_some() || self.can_sleep.is_some()
+    }
+
+    pub fn collider_material(&self) -> ColliderMaterial {
+        let default = ColliderMaterial::default();
+        ColliderMaterial {
+            friction: self.friction.unwrap_or(default.friction),
+            restitution: self.restitution.unwrap_or(default.restitution),
+            ..default
+        }
+    }
+
+    pub fn mass_properties(&self) -> ColliderMassProps {
+        ColliderMassProps::Density(self.density.unwrap_or(1.))
+    }
+
+    pub fn damping(&self) -> RigidBodyDamping {
+        let default = RigidBodyDamping::default();
+        RigidBodyDamping {
+            linear_damping: self.linear_damping.unwrap_or(default.linear_damping),
+            angular_damping: self.angular_damping.unwrap_or(default.angular_damping),
+        }
+    }
+
+    pub fn activation(&self) -> RigidBodyActivation {
+        match self.can_sleep {
+            Some(false) => RigidBodyActivation::cannot_sleep(),
+            _ => RigidBodyActivation::default(),
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use bevy::{app::AppExit, prelude::*};
+use bevy_egui::{egui, EguiContext};
+
+use crate::{
+    handles::Handles,
+    input::{Action, MenuFocus},
+    items::ItemBundle,
+    levels::{levels_loaded, Level},
+    progress::Progress,
+    AppState, CurrentLevel, FailureCause, LevelFailure, LevelStats, NextLevelEvent,
+    ResetLevelEvent,
+};
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(AppState::MainMenu).with_system(ui_main_menu.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::LevelComplete).with_system(ui_level_complete.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::LevelFailed).with_system(ui_level_failed.system()),
+        )
+        .add_system_set(SystemSet::on_update(AppState::GameEnd).with_system(ui_game_end.system()));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuPage {
+    Main,
+    LevelSelect,
+}
+
+impl Default for MenuPage {
+    fn default() -> Self {
+        MenuPage::Main
+    }
+}
+
+fn ui_main_menu(
+    egui_context: Res<EguiContext>,
+    actions: Res<Input<Action>>,
+    mut focus: Local<MenuFocus>,
+    mut page: Local<MenuPage>,
+    mut state: ResMut<State<AppState>>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut exit: EventWriter<AppExit>,
+    progress: Res<Progress>,
+    asset_server: Res<AssetServer>,
+    handles: Res<Handles>,
+    levels: Res<Assets<Level>>,
+    bundles: Res<Assets<ItemBundle>>,
+) {
+    let loaded = levels_loaded(&asset_server, &handles, &levels, &bundles);
+    let mut selected_level = None;
+    focus.update(&actions);
+
+    screen("Chevalchemy").show(egui_context.ctx(), |ui| {
+        ui.vertical_centered(|ui| match *page {
+            MenuPage::Main => {
+                if !loaded {
+                    ui.label("Loading...");
+                } else if focus.button(ui, "Play") {
+                    // Levels may have been removed since the progress was saved
+                    selected_level = Some(current_level.0.min(handles.levels.len() - 1));
+                }
+                if focus.button(ui, "Level Select") {
+                    *page = MenuPage::LevelSelect;
+                    focus.reset();
+                }
+                if focus.button(ui, "Quit") {
+                    exit.send(AppExit);
+                }
+            }
+            MenuPage::LevelSelect => {
+                for (i, level) in handles.levels.iter().enumerate() {
+                    match levels.get(level) {
+                        Some(level) if progress.is_unlocked(i) => {
+                            let text = match progress.record(&level.label) {
+                                Some(record) => match record.best_time {
+                                    Some(time) => format!(
+                                        "{} {} (best: {:.1}s)",
+                                        level.label,
+                                        stars(record.best_stars),
+                                        time
+                                    ),
+                                    None => format!("{} {}", level.label, stars(record.best_stars)),
+                                },
+                                None => level.label.clone(),
+                            };
+                            if focus.button(ui, &text) {
+                                selected_level = Some(i);
+                            }
+                        }
+                        Some(level) => {
+                            ui.label(format!("{} (locked)", level.label));
+                        }
+                        None => {
+                            ui.label("Loading...");
+                        }
+                    }
+                }
+                ui.separator();
+                if focus.button(ui, "Back") || actions.just_pressed(Action::Back) {
+                    *page = MenuPage::Main;
+                    focus.reset();
+                }
+            }
+        });
+    });
+
+    if let Some(level) = selected_level {
+        current_level.0 = level;
+        *page = MenuPage::Main;
+        focus.reset();
+        state.set(AppState::InGame).unwrap();
+    }
+}
+
+fn screen(title: &str) -> egui::Window<'_> {
+    egui::Window::new(title)
+        .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+        .collapsible(false)
+        .resizable(false)
+}
+
+fn stars(stars: u32) -> String {
+    let stars = stars.min(3) as usize;
+    format!("{}{}", "★".repeat(stars), "☆".repeat(3 - stars))
+}
+
+/// `eyed_vial` -> `eyed vial`
+fn type_name(ty: &str) -> String {
+    ty.replace('_', " ")
+}
+
+fn ui_level_complete(
+    egui_context: Res<EguiContext>,
+    actions: Res<Input<Action>>,
+    mut focus: Local<MenuFocus>,
+    mut state: ResMut<State<AppState>>,
+    mut next_level_events: EventWriter<NextLevelEvent>,
+    stats: Res<LevelStats>,
+    current_level: Res<CurrentLevel>,
+    handles: Res<Handles>,
+    levels: Res<Assets<Level>>,
+) {
+    let level = match levels.get(&handles.levels[current_level.0]) {
+        Some(level) => level,
+        None => return,
+    };
+    focus.update(&actions);
+
+    screen("Level complete").show(egui_context.ctx(), |ui| {
+        ui.vertical_centered(|ui| {
+            ui.label(format!("{} is ready!", level.label));
+            ui.heading(stars(level.par.stars(&stats)));
+            egui::Grid::new("level_stats").show(ui, |ui| {
+                ui.label("Time");
+                ui.label(format!("{:.1}s", stats.time));
+                ui.end_row();
+                ui.label("Resets");
+                ui.label(stats.resets().to_string());
+                ui.end_row();
+                ui.label("Items lost");
+                ui.label(stats.lost.to_string());
+                ui.end_row();
+            });
+            if focus.button(ui, "Continue") {
+                state.pop().unwrap();
+                next_level_events.send(NextLevelEvent);
+            }
+        });
+    });
+}
+
+fn ui_level_failed(
+    egui_context: Res<EguiContext>,
+    actions: Res<Input<Action>>,
+    mut focus: Local<MenuFocus>,
+    mut state: ResMut<State<AppState>>,
+    mut reset_level_events: EventWriter<ResetLevelEvent>,
+    failure: Res<LevelFailure>,
+) {
+    let expected: Vec<String> = failure.expected.iter().map(|ty| type_name(ty)).collect();
+    focus.update(&actions);
+
+    screen("Level failed").show(egui_context.ctx(), |ui| {
+        ui.vertical_centered(|ui| {
+            ui.label(format!("The recipe called for: {}", expected.join(" or ")));
+            match &failure.cause {
+                FailureCause::Dropped(ty) => ui.label(format!("But in went: {}", type_name(ty))),
+                FailureCause::Broken(ty) => ui.label(format!(
+                    "But a {} broke, and there aren't enough left",
+                    type_name(ty)
+                )),
+            };
+            if focus.button(ui, "Retry") {
+                state.pop().unwrap();
+                reset_level_events.send(ResetLevelEvent { reloaded: false });
+            }
+        });
+    });
+}
+
+fn ui_game_end(
+    egui_context: Res<EguiContext>,
+    actions: Res<Input<Action>>,
+    mut focus: Local<MenuFocus>,
+    mut state: ResMut<State<AppState>>,
+) {
+    focus.update(&actions);
+    screen("The end").show(egui_context.ctx(), |ui| {
+        ui.vertical_centered(|ui| {
+            ui.label("Every potion has been brewed. Thanks for playing!");
+            if focus.button(ui, "Main Menu") {
+                state.replace(AppState::MainMenu).unwrap();
+            }
+        });
+    });
+}
@@ -0,0 +1,273 @@
+use bevy::prelude::*;
+use bevy_rapier2d::{na, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{PI, TAU};
+
+/// How a piece of scenery moves, away from where it is placed and back.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct Motion {
+    pub kind: MotionKind,
+    #[serde(default)]
+    pub easing: Easing,
+    #[serde(default)]
+    pub repeat: Repeat,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) enum MotionKind {
+    /// Goes through `waypoints`, relative to where the piece is placed, at `speed` units
+    /// per second. `Loop` jumps back to the start unless the last waypoint is `(0, 0)`.
+    Path { waypoints: Vec<Vec2>, speed: f32 },
+    /// Turns all the way round at `speed` radians per second, counterclockwise if
+    /// positive.
+    Rotate { speed: f32 },
+    /// Turns by `angle` radians and back, at `speed` radians per second.
+    Swing { angle: f32, speed: f32 },
+    /// Moves by `offset` and back, at `speed` units per second.
+    Oscillate { offset: Vec2, speed: f32 },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub(crate) enum Easing {
+    Linear,
+    /// Slows down at both ends.
+    Smooth,
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Self::Linear
+    }
+}
+
+impl Easing {
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::Smooth => (1. - (t * PI).cos()) / 2.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub(crate) enum Repeat {
+    /// Moves once, then stays at the end.
+    Once,
+    /// Starts over from the beginning.
+    Loop,
+    /// Goes back the same way, then starts over.
+    PingPong,
+}
+
+impl Default for Repeat {
+    fn default() -> Self {
+        Self::Loop
+    }
+}
+
+impl MotionKind {
+    fn speed(&self) -> f32 {
+        match self {
+            MotionKind::Path { speed, .. }
+            | MotionKind::Rotate { speed }
+            | MotionKind::Swing { speed, .. }
+            | MotionKind::Oscillate { speed, .. } => *speed,
+        }
+    }
+
+    /// Units or radians covered in one go.
+    fn length(&self) -> f32 {
+        match self {
+            MotionKind::Path { waypoints, .. } => std::iter::once(Vec2::ZERO)
+                .chain(waypoints.iter().copied())
+                .zip(waypoints.iter())
+                .map(|(from, to)| from.distance(*to))
+                .sum(),
+            MotionKind::Rotate { .. } => TAU,
+            MotionKind::Swing { angle, .. } => 2. * angle.abs(),
+            MotionKind::Oscillate { offset, .. } => 2. * offset.length(),
+        }
+    }
+
+    /// The offset and rotation `progress` of the way through one go.
+    fn at(&self, progress: f32) -> (Vec2, f32) {
+        // Swings and oscillations come back on their own
+        let there_and_back = 1. - (2. * progress - 1.).abs();
+        match self {
+            MotionKind::Path { waypoints, .. } => {
+                let mut left = progress * self.length();
+                let mut from = Vec2::ZERO;
+                for &to in waypoints {
+                    let distance = from.distance(to);
+                    if left <= distance && distance > 0. {
+                        return (from.lerp(to, left / distance), 0.);
+                    }
+                    left -= distance;
+                    from = to;
+                }
+                (from, 0.)
+            }
+            MotionKind::Rotate { speed } => (Vec2::ZERO, progress * TAU * speed.signum()),
+            MotionKind::Swing { angle, .. } => (Vec2::ZERO, there_and_back * angle),
+            MotionKind::Oscillate { offset, .. } => (there_and_back * *offset, 0.),
+        }
+    }
+}
+
+impl Motion {
+    /// The offset and rotation from where the piece is placed, `time` seconds after it
+    /// was spawned.
+    pub fn pose(&self, time: f32) -> (Vec2, f32) {
+        let duration = self.kind.length() / self.kind.speed().abs();
+        if !duration.is_normal() {
+            return (Vec2::ZERO, 0.);
+        }
+
+        let goes = time / duration;
+        let progress = match self.repeat {
+            Repeat::Once => goes.min(1.),
+            Repeat::Loop => goes.fract(),
+            Repeat::PingPong => 1. - ((goes % 2.) - 1.).abs(),
+        };
+        self.kind.at(self.easing.apply(progress))
+    }
+
+    /// Catches what would make the piece stand still or misbehave.
+    pub fn check(&self) -> Result<(), String> {
+        let speed = self.kind.speed();
+        let positive = matches!(self.kind, MotionKind::Rotate { .. }) || speed > 0.;
+        if speed == 0. || !positive {
+            return Err(format!("speed {} is not positive", speed));
+        }
+        if self.kind.length() == 0. {
+            return Err("it doesn't go anywhere".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A kinematic piece of scenery, and how long it has been moving.
+pub struct Moving {
+    pub(crate) motion: Motion,
+    pub(crate) position: Vec2,
+    pub(crate) rotation: f32,
+    pub(crate) time: f32,
+}
+
+/// Moves kinematic scenery along, while the level is being played. Time is counted in
+/// physics steps, so that the scenery keeps pace with the bodies it carries.
+pub fn move_scenery(
+    integration_parameters: Res<IntegrationParameters>,
+    mut moving: Query<(&mut Moving, &mut RigidBodyPosition)>,
+) {
+    for (mut moving, mut body) in moving.iter_mut() {
+        moving.time += integration_parameters.dt;
+        let (offset, rotation) = moving.motion.pose(moving.time);
+        let position = moving.position + offset;
+        body.next_position = na::Isometry2::new(
+            na::Vector2::new(position.x, position.y),
+            moving.rotation + rotation,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(waypoints: &[(f32, f32)], speed: f32, repeat: Repeat) -> Motion {
+        Motion {
+            kind: MotionKind::Path {
+                waypoints: waypoints.iter().map(|&(x, y)| Vec2::new(x, y)).collect(),
+                speed,
+            },
+            easing: Easing::Linear,
+            repeat,
+        }
+    }
+
+    fn assert_near(a: Vec2, b: Vec2) {
+        assert!(a.abs_diff_eq(b, 1e-4), "{} is not {}", a, b);
+    }
+
+    #[test]
+    fn path_interpolation() {
+        // 20 units long, with zero-length segments at the start and in the middle
+        let motion = path(
+            &[(0., 0.), (10., 0.), (10., 0.), (10., 10.)],
+            10.,
+            Repeat::Once,
+        );
+        for &(progress, expected) in &[
+            (0., Vec2::ZERO),
+            (0.25, Vec2::new(5., 0.)),
+            (0.5, Vec2::new(10., 0.)),
+            (0.75, Vec2::new(10., 5.)),
+            (1., Vec2::new(10., 10.)),
+        ] {
+            assert_near(motion.kind.at(progress).0, expected);
+            assert_near(motion.pose(progress * 2.).0, expected);
+        }
+    }
+
+    #[test]
+    fn repeat() {
+        // One second to the end
+        let once = path(&[(10., 0.)], 10., Repeat::Once);
+        assert_near(once.pose(0.5).0, Vec2::new(5., 0.));
+        assert_near(once.pose(1.5).0, Vec2::new(10., 0.));
+        assert_near(once.pose(3.).0, Vec2::new(10., 0.));
+
+        let looping = path(&[(10., 0.)], 10., Repeat::Loop);
+        assert_near(looping.pose(0.5).0, Vec2::new(5., 0.));
+        assert_near(looping.pose(1.25).0, Vec2::new(2.5, 0.));
+        assert_near(looping.pose(3.75).0, Vec2::new(7.5, 0.));
+
+        let ping_pong = path(&[(10., 0.)], 10., Repeat::PingPong);
+        assert_near(ping_pong.pose(0.5).0, Vec2::new(5., 0.));
+        assert_near(ping_pong.pose(1.).0, Vec2::new(10., 0.));
+        assert_near(ping_pong.pose(1.25).0, Vec2::new(7.5, 0.));
+        assert_near(ping_pong.pose(2.).0, Vec2::ZERO);
+        assert_near(ping_pong.pose(2.25).0, Vec2::new(2.5, 0.));
+    }
+
+    #[test]
+    fn smooth_easing() {
+        let smooth = Easing::Smooth;
+        assert!(smooth.apply(0.).abs() < 1e-6);
+        assert!((smooth.apply(1.) - 1.).abs() < 1e-6);
+        assert!((smooth.apply(0.5) - 0.5).abs() < 1e-6);
+        // Slower than linear near the ends
+        assert!(smooth.apply(0.1) < 0.1);
+        assert!(smooth.apply(0.9) > 0.9);
+    }
+
+    #[test]
+    fn check() {
+        assert!(path(&[(10., 0.)], 10., Repeat::Loop).check().is_ok());
+        let rotate = |speed| Motion {
+            kind: MotionKind::Rotate { speed },
+            easing: Easing::Linear,
+            repeat: Repeat::Loop,
+        };
+        assert!(rotate(-1.).check().is_ok());
+
+        assert!(path(&[(10., 0.)], 0., Repeat::Loop).check().is_err());
+        assert!(path(&[(10., 0.)], -10., Repeat::Loop).check().is_err());
+        assert!(rotate(0.).check().is_err());
+
+        assert!(path(&[], 10., Repeat::Loop).check().is_err());
+        assert!(path(&[(0., 0.), (0., 0.)], 10., Repeat::Loop)
+            .check()
+            .is_err());
+        let oscillate = Motion {
+            kind: MotionKind::Oscillate {
+                offset: Vec2::ZERO,
+                speed: 10.,
+            },
+            easing: Easing::Linear,
+            repeat: Repeat::Loop,
+        };
+        assert!(oscillate.check().is_err());
+    }
+}
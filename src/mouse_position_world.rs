@@ -5,7 +5,7 @@ pub struct MousePositionWorldPlugin;
 impl Plugin for MousePositionWorldPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.insert_resource(MousePositionWorld::default())
-            .add_system(mouse_position.system());
+            .add_system(mouse_position.system().label("mouse_position"));
     }
 }
 
@@ -0,0 +1,64 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::storage::Storage;
+
+const STORAGE_KEY: &str = "progress";
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LevelRecord {
+    /// The fastest completion, in seconds.
+    pub best_time: Option<f32>,
+    pub best_stars: u32,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Progress {
+    pub unlocked: usize,
+    /// Completed levels, by label.
+    pub levels: HashMap<String, LevelRecord>,
+}
+
+impl Progress {
+    /// Loads the saved progress, starting over if it is missing or corrupt.
+    pub fn load(storage: &dyn Storage) -> Self {
+        match storage.load(STORAGE_KEY) {
+            Some(progress) => ron::de::from_str(&progress).unwrap_or_else(|e| {
+                eprintln!("error: could not read saved progress, starting over: {}", e);
+                Self::default()
+            }),
+            None => Self::default(),
+        }
+    }
+
+    pub fn save(&self, storage: &dyn Storage) -> Result<()> {
+        let progress = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())?;
+        storage.save(STORAGE_KEY, &progress)
+    }
+
+    pub fn is_unlocked(&self, level: usize) -> bool {
+        level <= self.unlocked
+    }
+
+    pub fn unlock(&mut self, level: usize) {
+        self.unlocked = self.unlocked.max(level);
+    }
+
+    pub fn record(&self, label: &str) -> Option<&LevelRecord> {
+        self.levels.get(label)
+    }
+
+    /// Marks a level as completed, keeping the best time and star rating.
+    pub fn complete(&mut self, label: &str, time: f32, stars: u32) {
+        let record = self.levels.entry(label.to_string()).or_default();
+        if record.best_time.map_or(true, |best| time < best) {
+            record.best_time = Some(time);
+        }
+        record.best_stars = record.best_stars.max(stars);
+    }
+}
+
+pub struct ProgressStorage(pub Box<dyn Storage>);
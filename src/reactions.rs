@@ -0,0 +1,230 @@
+use anyhow::Result;
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::world::FromWorld,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asset_errors::AssetErrors,
+    asset_files::{parse_entries, AssetRef},
+    items::{item_handle, Item, ItemFile},
+    recipe::{CurrentRecipe, RecipeStep},
+    ItemType,
+};
+
+/// The file that holds every reaction, which happen in all cauldrons of all levels.
+pub(crate) const REACTIONS_FILE: &str = "reactions.reactions";
+
+#[derive(Clone, Deserialize, Serialize)]
+pub(crate) struct ReactionFile {
+    pub label: String,
+    /// The last items to go in, written like a recipe.
+    pub sequence: Vec<RecipeStep>,
+    pub effects: Vec<EffectFile>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub(crate) enum EffectFile {
+    Color(f32, f32, f32),
+    Bubble,
+    Puff,
+    Spawn(AssetRef<ItemFile>, Vec2),
+    Forgive,
+}
+
+#[derive(Debug, Clone)]
+pub enum Effect {
+    /// Tints the smoke of the mixture from now on.
+    Color(Color),
+    Bubble,
+    Puff,
+    /// Spawns an item, relative to the cauldron.
+    Spawn(Handle<Item>, Vec2),
+    /// Keeps the item that caused the reaction from failing the level.
+    Forgive,
+}
+
+#[derive(Debug, Clone)]
+pub struct Reaction {
+    pub label: String,
+    pub sequence: Vec<RecipeStep>,
+    pub effects: Vec<Effect>,
+}
+
+impl Reaction {
+    /// Whether the last items that went in match the sequence.
+    pub fn matches(&self, history: &[ItemType]) -> bool {
+        sequence_matches(&self.sequence, history)
+    }
+}
+
+/// Whether the last items in `history` match `sequence`, which is written like a recipe.
+pub(crate) fn sequence_matches(sequence: &[RecipeStep], history: &[ItemType]) -> bool {
+    let length: usize = sequence
+        .iter()
+        .flat_map(|step| step.ingredients())
+        .map(|ingredient| ingredient.count)
+        .sum();
+    if length == 0 || length > history.len() {
+        return false;
+    }
+
+    let mut sequence = CurrentRecipe::new(sequence);
+    history[history.len() - length..]
+        .iter()
+        .all(|ty| sequence.add(ty))
+}
+
+#[derive(Debug, Clone, Default, TypeUuid)]
+#[uuid = "9b766f44-da0c-4443-93a6-908afe88cd4e"]
+pub struct Reactions(pub Vec<Reaction>);
+
+/// What went into a cauldron since the level started.
+#[derive(Debug, Clone, Default)]
+pub struct Mixture {
+    pub history: Vec<ItemType>,
+    pub color: Option<Color>,
+}
+
+pub struct ReactionsLoader {
+    errors: AssetErrors,
+}
+
+impl FromWorld for ReactionsLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            errors: world.get_resource::<AssetErrors>().unwrap().clone(),
+        }
+    }
+}
+
+const FILE_EXTENSIONS: &[&str] = &["reactions"];
+
+impl AssetLoader for ReactionsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext<'_>,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let result = load_reactions(bytes, load_context);
+            self.errors.report(load_context.path(), &result);
+            result
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        FILE_EXTENSIONS
+    }
+}
+
+fn load_reactions(bytes: &[u8], load_context: &mut LoadContext) -> Result<()> {
+    let reactions = parse_entries::<ReactionFile>(bytes)?
+        .into_vec()
+        .into_iter()
+        .map(|reaction| {
+            let ReactionFile {
+                label,
+                sequence,
+                effects,
+            } = reaction;
+
+            let effects = effects
+                .into_iter()
+                .enumerate()
+                .map(|(i, effect)| {
+                    Ok(match effect {
+                        EffectFile::Color(r, g, b) => Effect::Color(Color::rgb(r, g, b)),
+                        EffectFile::Bubble => Effect::Bubble,
+                        EffectFile::Puff => Effect::Puff,
+                        EffectFile::Spawn(item, position) => {
+                            Effect::Spawn(item_handle(item, &label, i, load_context)?, position)
+                        }
+                        EffectFile::Forgive => Effect::Forgive,
+                    })
+                })
+                .collect::<Result<_>>()?;
+
+            Ok(Reaction {
+                label,
+                sequence,
+                effects,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    load_context.set_default_asset(LoadedAsset::new(Reactions(reactions)));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(sequence: &str, history: &[&str]) -> bool {
+        let sequence: Vec<RecipeStep> = ron::de::from_str(sequence).unwrap();
+        let history: Vec<ItemType> = history.iter().map(|ty| ty.to_string()).collect();
+        sequence_matches(&sequence, &history)
+    }
+
+    #[test]
+    fn matches_the_last_items() {
+        assert!(matches(r#"["a", "b"]"#, &["a", "b"]));
+        assert!(matches(r#"["a", "b"]"#, &["c", "b", "a", "b"]));
+        assert!(!matches(r#"["a", "b"]"#, &["a", "b", "c"]));
+        assert!(!matches(r#"["a", "b"]"#, &["b", "a"]));
+        assert!(!matches(r#"["a", "b"]"#, &["b"]));
+    }
+
+    #[test]
+    fn matches_steps_like_a_recipe() {
+        assert!(matches(r#"[["a", "b"]]"#, &["b", "a"]));
+        assert!(matches(r#"["a|b", "2x c"]"#, &["b", "c", "c"]));
+        assert!(!matches(r#"["a|b", "2x c"]"#, &["b", "c"]));
+        assert!(!matches(r#"["a|b", "2x c"]"#, &["c", "c", "c"]));
+    }
+
+    #[test]
+    fn empty_sequence_never_matches() {
+        assert!(!matches("[]", &[]));
+        assert!(!matches("[]", &["a"]));
+    }
+
+    #[test]
+    fn parse_effects() {
+        let reaction: ReactionFile = ron::de::from_str(
+            r#"(
+                label: "skull",
+                sequence: ["skull"],
+                effects: [
+                    Color(0.5, 0., 1.),
+                    Bubble,
+                    Spawn("items.items#bone", (0., 50.)),
+                    Spawn((
+                        type: "bone",
+                        texture_atlas: "items.atlas",
+                        texture_index: 3,
+                        colliders: [((0., 0.), Ball(5.))],
+                    ), (0., 50.)),
+                    Forgive,
+                ],
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(reaction.label, "skull");
+        assert!(matches!(
+            reaction.effects.as_slice(),
+            [
+                EffectFile::Color(_, _, _),
+                EffectFile::Bubble,
+                EffectFile::Spawn(AssetRef::Path(path), _),
+                EffectFile::Spawn(AssetRef::Inline(item), position),
+                EffectFile::Forgive,
+            ] if path == "items.items#bone" && item.ty == "bone" && *position == Vec2::new(0., 50.)
+        ));
+    }
+}
@@ -0,0 +1,433 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, convert::TryFrom, fmt, str::FromStr};
+
+use crate::ItemType;
+
+/// `count` items, each of which can be any of `types`.
+///
+/// Written as `"cube"`, `"3x cube"` or `"red_vial|blue_vial"` in level files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ingredient {
+    pub types: Vec<ItemType>,
+    pub count: usize,
+}
+
+impl Ingredient {
+    fn slots(&self) -> impl Iterator<Item = Slot> + '_ {
+        (0..self.count).map(move |_| Slot {
+            types: self.types.clone(),
+            filled: false,
+        })
+    }
+}
+
+impl FromStr for Ingredient {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let digits = s.chars().take_while(char::is_ascii_digit).count();
+        let (count, types) = match s[digits..].trim_start().strip_prefix(&['x', '×'][..]) {
+            Some(types) if digits > 0 => (
+                s[..digits]
+                    .parse()
+                    .map_err(|_| format!("invalid count in '{}'", s))?,
+                types,
+            ),
+            _ => (1, s),
+        };
+
+        let types: Vec<ItemType> = types.split('|').map(|ty| ty.trim().to_string()).collect();
+        if count == 0 || types.iter().any(String::is_empty) {
+            return Err(format!("invalid ingredient '{}'", s));
+        }
+        Ok(Self { types, count })
+    }
+}
+
+impl fmt::Display for Ingredient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count != 1 {
+            write!(f, "{}x ", self.count)?;
+        }
+        write!(f, "{}", self.types.join("|"))
+    }
+}
+
+/// A step of a recipe: a single ingredient, or several that can go in in any order.
+///
+/// Written as a string or as a list of strings in level files.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "RecipeStepFile", into = "RecipeStepFile")]
+pub enum RecipeStep {
+    Ingredient(Ingredient),
+    AnyOrder(Vec<Ingredient>),
+}
+
+impl RecipeStep {
+    pub fn ingredients(&self) -> &[Ingredient] {
+        match self {
+            RecipeStep::Ingredient(ingredient) => std::slice::from_ref(ingredient),
+            RecipeStep::AnyOrder(ingredients) => ingredients,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum RecipeStepFile {
+    Ingredient(String),
+    AnyOrder(Vec<String>),
+}
+
+impl TryFrom<RecipeStepFile> for RecipeStep {
+    type Error = String;
+
+    fn try_from(file: RecipeStepFile) -> Result<Self, Self::Error> {
+        match file {
+            RecipeStepFile::Ingredient(ingredient) => Ok(Self::Ingredient(ingredient.parse()?)),
+            RecipeStepFile::AnyOrder(ingredients) => Ok(Self::AnyOrder(
+                ingredients
+                    .iter()
+                    .map(|ingredient| ingredient.parse())
+                    .collect::<Result<_, _>>()?,
+            )),
+        }
+    }
+}
+
+impl From<RecipeStep> for RecipeStepFile {
+    fn from(step: RecipeStep) -> Self {
+        match step {
+            RecipeStep::Ingredient(ingredient) => Self::Ingredient(ingredient.to_string()),
+            RecipeStep::AnyOrder(ingredients) => {
+                Self::AnyOrder(ingredients.iter().map(ToString::to_string).collect())
+            }
+        }
+    }
+}
+
+/// A single item to put in the cauldron.
+#[derive(Debug, Clone)]
+pub struct Slot {
+    pub types: Vec<ItemType>,
+    pub filled: bool,
+}
+
+impl Slot {
+    fn accepts(&self, ty: &str) -> bool {
+        self.types.iter().any(|other| other == ty)
+    }
+}
+
+/// Progress through a recipe.
+///
+/// Each step is a set of slots which can be filled in any order; a step must be
+/// complete before the next one can start.
+#[derive(Debug, Clone, Default)]
+pub struct CurrentRecipe {
+    steps: Vec<Vec<Slot>>,
+    next_step: usize,
+    /// The items that went in during the current step. Which slots they fill can change
+    /// as more go in, so that `a` then `b` fill `[b|c, a|b]` in either order.
+    added: Vec<ItemType>,
+}
+
+impl CurrentRecipe {
+    pub fn new(recipe: &[RecipeStep]) -> Self {
+        let steps = recipe
+            .iter()
+            .map(|step| match step {
+                RecipeStep::Ingredient(ingredient) => ingredient.slots().collect(),
+                RecipeStep::AnyOrder(ingredients) => {
+                    ingredients.iter().flat_map(Ingredient::slots).collect()
+                }
+            })
+            .collect();
+        Self {
+            steps,
+            next_step: 0,
+            added: Vec::new(),
+        }
+    }
+
+    pub fn steps(&self) -> &[Vec<Slot>] {
+        &self.steps
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.next_step >= self.steps.len()
+    }
+
+    /// The item types that would be accepted next.
+    pub fn expected(&self) -> Vec<&ItemType> {
+        let mut expected = Vec::new();
+        let mut items: Vec<&str> = self.added.iter().map(String::as_str).collect();
+        let slots = self.steps.get(self.next_step).into_iter().flatten();
+        for ty in slots.flat_map(|slot| &slot.types) {
+            items.push(ty);
+            if !expected.contains(&ty) && self.fit(&items).is_some() {
+                expected.push(ty);
+            }
+            items.pop();
+        }
+        expected
+    }
+
+    /// Fills a slot of the current step with an item of type `ty`, if one accepts it.
+    pub fn add(&mut self, ty: &str) -> bool {
+        let mut items: Vec<&str> = self.added.iter().map(String::as_str).collect();
+        items.push(ty);
+        let fit = match self.fit(&items) {
+            Some(fit) => fit,
+            None => return false,
+        };
+
+        let step = &mut self.steps[self.next_step];
+        for slot in step.iter_mut() {
+            slot.filled = false;
+        }
+        for slot in fit {
+            step[slot].filled = true;
+        }
+        self.added.push(ty.to_string());
+        if self.added.len() == step.len() {
+            self.next_step += 1;
+            self.added.clear();
+        }
+        true
+    }
+
+    /// Which slot of the current step each of `items` fills, if they all fit at once.
+    fn fit(&self, items: &[&str]) -> Option<Vec<usize>> {
+        let step = self.steps.get(self.next_step)?;
+        let accepts: Vec<Vec<usize>> = items
+            .iter()
+            .map(|ty| {
+                (0..step.len())
+                    .filter(|&slot| step[slot].accepts(ty))
+                    .collect()
+            })
+            .collect();
+        bipartite_match(&accepts, &vec![1; step.len()])
+    }
+}
+
+/// Whether the slots left in `recipes` can still be filled, given how many items of each
+/// type are `available`. Each item fills one slot, even if several would take it.
+pub fn can_complete<'a>(
+    recipes: impl IntoIterator<Item = &'a CurrentRecipe>,
+    available: &HashMap<&str, usize>,
+) -> bool {
+    let recipes: Vec<&CurrentRecipe> = recipes.into_iter().collect();
+
+    // Where items can come from: those still around, which any slot can take, and those
+    // already in a cauldron, which only the slots of its current step can
+    let mut sources: Vec<(Option<usize>, &str)> = Vec::new();
+    let mut capacity = Vec::new();
+    for (&ty, &count) in available {
+        sources.push((None, ty));
+        capacity.push(count);
+    }
+    for (i, recipe) in recipes.iter().enumerate() {
+        for ty in &recipe.added {
+            match sources
+                .iter()
+                .position(|&source| source == (Some(i), ty.as_str()))
+            {
+                Some(source) => capacity[source] += 1,
+                None => {
+                    sources.push((Some(i), ty.as_str()));
+                    capacity.push(1);
+                }
+            }
+        }
+    }
+
+    // Items already in fill the current step's slots some way or other, so every slot
+    // getting an item means the added ones can be part of it too
+    let mut accepts = Vec::new();
+    for (i, recipe) in recipes.iter().enumerate() {
+        for (step, slots) in recipe.steps.iter().enumerate().skip(recipe.next_step) {
+            for slot in slots {
+                let usable = |&(recipe, ty): &(Option<usize>, &str)| {
+                    let reachable = match recipe {
+                        Some(recipe) => recipe == i && step == recipes[i].next_step,
+                        None => true,
+                    };
+                    reachable && slot.accepts(ty)
+                };
+                accepts.push(
+                    (0..sources.len())
+                        .filter(|&source| usable(&sources[source]))
+                        .collect(),
+                );
+            }
+        }
+    }
+    bipartite_match(&accepts, &capacity).is_some()
+}
+
+/// Gives each left node one of the right nodes it `accepts`, with no right node taking
+/// more than its `capacity`, if that can be done. Returns the right node of each left one.
+///
+/// Finds augmenting paths one left node at a time, as in Kuhn's algorithm.
+fn bipartite_match(accepts: &[Vec<usize>], capacity: &[usize]) -> Option<Vec<usize>> {
+    fn augment(
+        left: usize,
+        accepts: &[Vec<usize>],
+        capacity: &[usize],
+        taken: &mut [Vec<usize>],
+        visited: &mut [bool],
+    ) -> bool {
+        for &right in &accepts[left] {
+            if visited[right] {
+                continue;
+            }
+            visited[right] = true;
+            if taken[right].len() < capacity[right] {
+                taken[right].push(left);
+                return true;
+            }
+            // Make room by moving one of the left nodes there elsewhere
+            for i in 0..taken[right].len() {
+                if augment(taken[right][i], accepts, capacity, taken, visited) {
+                    taken[right][i] = left;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    let mut taken = vec![Vec::new(); capacity.len()];
+    for left in 0..accepts.len() {
+        let mut visited = vec![false; capacity.len()];
+        if !augment(left, accepts, capacity, &mut taken, &mut visited) {
+            return None;
+        }
+    }
+
+    let mut matched = vec![0; accepts.len()];
+    for (right, lefts) in taken.iter().enumerate() {
+        for &left in lefts {
+            matched[left] = right;
+        }
+    }
+    Some(matched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ingredient(s: &str) -> Ingredient {
+        s.parse().unwrap()
+    }
+
+    fn recipe(steps: &[&[&str]]) -> CurrentRecipe {
+        let steps: Vec<RecipeStep> = steps
+            .iter()
+            .map(|step| RecipeStep::AnyOrder(step.iter().map(|s| ingredient(s)).collect()))
+            .collect();
+        CurrentRecipe::new(&steps)
+    }
+
+    #[test]
+    fn parse_ingredients() {
+        assert_eq!(
+            ingredient("cube"),
+            Ingredient {
+                types: vec!["cube".to_string()],
+                count: 1,
+            }
+        );
+        assert_eq!(ingredient(" 3x cube ").count, 3);
+        assert_eq!(ingredient("2 × bone").count, 2);
+        assert_eq!(
+            ingredient("red_vial | blue_vial").types,
+            ["red_vial", "blue_vial"]
+        );
+        assert_eq!(ingredient("10x a|b").to_string(), "10x a|b");
+        // A name that starts with a digit but has no count
+        assert_eq!(ingredient("3d_cube").types, ["3d_cube"]);
+
+        for invalid in ["", "0x cube", "2x ", "a||b", "a|"].iter() {
+            assert!(invalid.parse::<Ingredient>().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn parse_steps() {
+        let steps: Vec<RecipeStep> = ron::de::from_str(r#"["cube", ["2x bone", "a|b"]]"#).unwrap();
+        assert_eq!(
+            steps,
+            [
+                RecipeStep::Ingredient(ingredient("cube")),
+                RecipeStep::AnyOrder(vec![ingredient("2x bone"), ingredient("a|b")]),
+            ]
+        );
+        assert!(ron::de::from_str::<RecipeStep>(r#""0x cube""#).is_err());
+    }
+
+    #[test]
+    fn any_order_moves_items_between_slots() {
+        let mut current = recipe(&[&["a|b", "b|c"]]);
+        assert!(current.add("b"));
+        assert_eq!(current.expected(), ["a", "b", "c"]);
+        assert!(current.add("a"));
+        assert!(current.is_complete());
+
+        let mut current = recipe(&[&["a|b", "b|c"]]);
+        assert!(current.add("b"));
+        assert!(current.add("c"));
+        assert!(current.is_complete());
+    }
+
+    #[test]
+    fn any_order_rejects_what_cannot_fit() {
+        let mut current = recipe(&[&["a|b", "b"], &["c"]]);
+        assert!(current.add("a"));
+        assert!(!current.add("a"));
+        assert!(!current.add("c"));
+        assert_eq!(current.expected(), ["b"]);
+        assert!(current.add("b"));
+        assert_eq!(current.expected(), ["c"]);
+        assert!(current.add("c"));
+        assert!(current.is_complete());
+        assert!(!current.add("c"));
+    }
+
+    #[test]
+    fn any_order_counts() {
+        let mut current = recipe(&[&["2x a|b", "a"]]);
+        assert!(current.add("a"));
+        assert!(current.add("a"));
+        assert_eq!(current.expected(), ["a", "b"]);
+        assert!(current.add("a"));
+        assert!(current.is_complete());
+    }
+
+    #[test]
+    fn can_complete_shares_items_between_slots() {
+        let available = |items: &[(&'static str, usize)]| items.iter().copied().collect();
+        let current = recipe(&[&["a|b", "b|c"]]);
+        assert!(!can_complete(&[current.clone()], &available(&[("b", 1)])));
+        assert!(can_complete(&[current.clone()], &available(&[("b", 2)])));
+        assert!(can_complete(&[current], &available(&[("a", 1), ("c", 1)])));
+
+        // The `b` that went in can move over to make room for an `a`
+        let mut current = recipe(&[&["a|b", "b|c"], &["d"]]);
+        assert!(current.add("b"));
+        assert!(can_complete(
+            &[current.clone()],
+            &available(&[("a", 1), ("d", 1)])
+        ));
+        assert!(!can_complete(&[current], &available(&[("a", 1)])));
+
+        // Two cauldrons draw from the same items
+        let recipes = [recipe(&[&["a"]]), recipe(&[&["a|b"]])];
+        assert!(!can_complete(&recipes, &available(&[("a", 1)])));
+        assert!(can_complete(&recipes, &available(&[("a", 1), ("b", 1)])));
+    }
+}
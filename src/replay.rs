@@ -0,0 +1,234 @@
+use anyhow::Result;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    handles::Handles,
+    items::ItemBundle,
+    levels::{levels_loaded, Level},
+    tools::ToolControl,
+    AppState, CurrentLevel, GameRng, HoofTarget, ResetLevelEvent,
+};
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let pending = replay_argument().and_then(|path| match Replay::from_file(&path) {
+            Ok(replay) => Some(replay),
+            Err(e) => {
+                eprintln!("error: could not load replay '{}': {:#}", path, e);
+                None
+            }
+        });
+
+        app.insert_resource(HoofReplay {
+            mode: ReplayMode::Recording,
+            replay: Replay::default(),
+            pending,
+            time: 0.,
+        })
+        .add_system_set(
+            SystemSet::on_update(AppState::MainMenu).with_system(start_pending_replay.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(replay_reset_events.system())
+                .with_system(
+                    replay_hoof
+                        .system()
+                        .label("replay_hoof")
+                        .after("hoof_target")
+                        .after("pick_tool"),
+                )
+                .with_system(save_replay.system()),
+        );
+    }
+}
+
+/// The hoof target and what the tools were asked to do over one attempt at a level.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Replay {
+    pub level: String,
+    pub seed: u64,
+    /// One sample per frame, so replays don't assume any frame rate.
+    pub hoof: Vec<HoofSample>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct HoofSample {
+    /// Physics time into the attempt, in seconds.
+    pub time: f32,
+    pub target: Vec2,
+    /// The label of the tool to hold, or the first one the level allows if empty.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub tool: String,
+    /// Whether `Action::Grab` is held.
+    #[serde(default)]
+    pub grab: bool,
+}
+
+impl Replay {
+    /// The sample in effect `time` seconds into the attempt, which is the last one taken
+    /// by then.
+    pub fn sample_at(&self, time: f32) -> Option<&HoofSample> {
+        let taken = self.hoof.partition_point(|sample| sample.time <= time);
+        self.hoof.get(taken.saturating_sub(1))
+    }
+
+    /// When the last sample was taken.
+    pub fn duration(&self) -> f32 {
+        self.hoof.last().map_or(0., |sample| sample.time)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(ron::de::from_bytes(&std::fs::read(path)?)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let replay = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())?;
+        std::fs::write(path, replay)?;
+        Ok(())
+    }
+}
+
+fn replay_argument() -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--replay");
+    args.next();
+    args.next()
+}
+
+enum ReplayMode {
+    Recording,
+    Playing { started: bool },
+}
+
+struct HoofReplay {
+    mode: ReplayMode,
+    replay: Replay,
+    pending: Option<Replay>,
+    /// Physics time into the attempt.
+    time: f32,
+}
+
+fn start_pending_replay(
+    mut hoof_replay: ResMut<HoofReplay>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut state: ResMut<State<AppState>>,
+    asset_server: Res<AssetServer>,
+    handles: Res<Handles>,
+    levels: Res<Assets<Level>>,
+    bundles: Res<Assets<ItemBundle>>,
+) {
+    if hoof_replay.pending.is_none() || !levels_loaded(&asset_server, &handles, &levels, &bundles) {
+        return;
+    }
+
+    let replay = hoof_replay.pending.take().unwrap();
+    let index = handles
+        .levels
+        .iter()
+        .position(|level| levels.get(level).unwrap().label == replay.level);
+
+    match index {
+        Some(index) => {
+            current_level.0 = index;
+            hoof_replay.replay = replay;
+            hoof_replay.mode = ReplayMode::Playing { started: false };
+            state.set(AppState::InGame).unwrap();
+        }
+        None => eprintln!("error: could not find level '{}' for replay", replay.level),
+    }
+}
+
+fn replay_reset_events(
+    mut reset_level_events: EventReader<ResetLevelEvent>,
+    mut hoof_replay: ResMut<HoofReplay>,
+    mut rng: ResMut<GameRng>,
+    current_level: Res<CurrentLevel>,
+    handles: Res<Handles>,
+    levels: Res<Assets<Level>>,
+) {
+    if reset_level_events.iter().last().is_none() {
+        return;
+    }
+
+    let HoofReplay {
+        mode, replay, time, ..
+    } = &mut *hoof_replay;
+    *time = 0.;
+    match mode {
+        ReplayMode::Playing { started } if !*started => {
+            *started = true;
+            rng.reseed(replay.seed);
+        }
+        _ => {
+            // Any other reset starts a new attempt, which is recorded from scratch
+            let seed = rand::random();
+            rng.reseed(seed);
+            *mode = ReplayMode::Recording;
+            *replay = Replay {
+                level: levels
+                    .get(&handles.levels[current_level.0])
+                    .map(|level| level.label.clone())
+                    .unwrap_or_default(),
+                seed,
+                hoof: Vec::new(),
+            };
+        }
+    }
+}
+
+/// Records the hoof target and tool control, or sets them from the replay, at the
+/// physics time of the step about to be taken.
+fn replay_hoof(
+    mut hoof_replay: ResMut<HoofReplay>,
+    mut hoof_target: ResMut<HoofTarget>,
+    mut tool_control: ResMut<ToolControl>,
+    integration_parameters: Res<IntegrationParameters>,
+) {
+    let HoofReplay {
+        mode, replay, time, ..
+    } = &mut *hoof_replay;
+    if let ReplayMode::Playing { .. } = mode {
+        match replay.sample_at(*time) {
+            Some(sample) if *time <= replay.duration() => {
+                hoof_target.0 = sample.target;
+                if !sample.tool.is_empty() {
+                    tool_control.tool = sample.tool.clone();
+                }
+                tool_control.grab = sample.grab;
+            }
+            // The player takes over where the replay ends
+            _ => *mode = ReplayMode::Recording,
+        }
+    }
+    if let ReplayMode::Recording = mode {
+        replay.hoof.push(HoofSample {
+            time: *time,
+            target: hoof_target.0,
+            tool: tool_control.tool.clone(),
+            grab: tool_control.grab,
+        });
+    }
+    *time += integration_parameters.dt;
+}
+
+fn save_replay(keys: Res<Input<KeyCode>>, hoof_replay: Res<HoofReplay>) {
+    if !keys.just_pressed(KeyCode::F5) {
+        return;
+    }
+
+    let replay = &hoof_replay.replay;
+    let dir = PathBuf::from("replays");
+    let path = dir.join(format!("{}-{}.replay", replay.level, replay.seed));
+    let result = std::fs::create_dir_all(&dir)
+        .map_err(Into::into)
+        .and_then(|_| replay.save(&path));
+    match result {
+        Ok(()) => eprintln!("saved replay to {}", path.display()),
+        Err(e) => eprintln!("error: could not save replay: {:#}", e),
+    }
+}
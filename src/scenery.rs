@@ -0,0 +1,195 @@
+use anyhow::Result;
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::world::FromWorld,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use bevy_rapier2d::{na, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asset_errors::AssetErrors,
+    asset_files::{inline_label, parse_entries, set_labeled_asset, Entries},
+    items::{check_shapes, insert_colliders, Collider},
+    material::Material,
+    motion::{Motion, Moving},
+    sprite::{sprite_asset, SpriteFile, SpriteHandle},
+    LevelEntity,
+};
+
+/// Static level geometry, such as shelves, walls and ramps.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct SceneryFile {
+    #[serde(default)]
+    pub label: String,
+    pub pieces: Vec<PieceFile>,
+}
+
+/// One body of a scenery.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct PieceFile {
+    pub position: Vec2,
+    /// Radians, counterclockwise
+    #[serde(default)]
+    pub rotation: f32,
+    #[serde(default, skip_serializing_if = "Material::is_empty")]
+    pub material: Material,
+    pub colliders: Vec<Collider>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sprite: Option<SpriteFile>,
+    /// Makes it a kinematic body that moves on its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion: Option<Motion>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Piece {
+    pub(crate) file: PieceFile,
+    sprite: Option<SpriteHandle>,
+}
+
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "0f1d7a57-3c5e-4b8f-a6e2-5d9c4e0b7a13"]
+pub struct Scenery {
+    pub label: String,
+    pub pieces: Vec<Piece>,
+    /// What it was loaded from, for the editor to write it back.
+    pub(crate) file: SceneryFile,
+}
+
+impl Scenery {
+    pub fn spawn(&self, commands: &mut Commands, position: Vec2) {
+        for piece in &self.pieces {
+            piece.spawn(commands, position);
+        }
+    }
+}
+
+impl Piece {
+    fn spawn(&self, commands: &mut Commands, offset: Vec2) {
+        let PieceFile {
+            position,
+            rotation,
+            material,
+            colliders,
+            motion,
+            ..
+        } = &self.file;
+        let position = offset + *position;
+
+        let mut entity = commands.spawn();
+        if let Some(sprite) = &self.sprite {
+            // Behind the items
+            sprite.insert(&mut entity, Transform::from_xyz(0., 0., 2.));
+        }
+        let body_type = match motion {
+            Some(motion) => {
+                entity.insert(Moving {
+                    motion: motion.clone(),
+                    position,
+                    rotation: *rotation,
+                    time: 0.,
+                });
+                RigidBodyType::KinematicPositionBased
+            }
+            None => RigidBodyType::Static,
+        };
+        entity
+            .insert_bundle(RigidBodyBundle {
+                body_type,
+                position: na::Isometry2::new(na::Vector2::new(position.x, position.y), *rotation)
+                    .into(),
+                ..Default::default()
+            })
+            .insert(RigidBodyPositionSync::Discrete)
+            .insert(LevelEntity);
+        insert_colliders(&mut entity, colliders, material);
+    }
+}
+
+pub struct SceneryLoader {
+    errors: AssetErrors,
+}
+
+impl FromWorld for SceneryLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            errors: world.get_resource::<AssetErrors>().unwrap().clone(),
+        }
+    }
+}
+
+const FILE_EXTENSIONS: &[&str] = &["scenery"];
+
+impl AssetLoader for SceneryLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext<'_>,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let result = load_scenery(bytes, load_context);
+            self.errors.report(load_context.path(), &result);
+            result
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        FILE_EXTENSIONS
+    }
+}
+
+fn load_scenery(bytes: &[u8], load_context: &mut LoadContext) -> Result<()> {
+    match parse_entries::<SceneryFile>(bytes)? {
+        Entries::One(scenery) => {
+            let asset = scenery_asset(scenery, "", load_context)?;
+            load_context.set_default_asset(LoadedAsset::new(asset));
+        }
+        Entries::Many(sceneries) => {
+            for scenery in sceneries {
+                let label = scenery.label.clone();
+                let asset = scenery_asset(scenery, &label, load_context)?;
+                set_labeled_asset(load_context, &label, asset)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds a scenery asset, adding the materials for its sprites as assets labeled under
+/// `parent`.
+pub(crate) fn scenery_asset(
+    scenery: SceneryFile,
+    parent: &str,
+    load_context: &mut LoadContext,
+) -> Result<Scenery> {
+    let file = scenery.clone();
+    let SceneryFile { label, pieces } = scenery;
+
+    let pieces = pieces
+        .into_iter()
+        .enumerate()
+        .map(|(i, piece)| {
+            let label = inline_label(parent, &format!("piece{}", i), i);
+            check_shapes(piece.colliders.iter().map(|c| &c.shape), &label)?;
+            let sprite = piece
+                .sprite
+                .as_ref()
+                .map(|sprite| sprite_asset(sprite, &label, load_context))
+                .transpose()?;
+            Ok(Piece {
+                file: piece,
+                sprite,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Scenery {
+        label,
+        pieces,
+        file,
+    })
+}
@@ -0,0 +1,440 @@
+use anyhow::{bail, Context, Result};
+use bevy::{asset::AssetServerSettings, prelude::*};
+use bevy_rapier2d::{physics::TimestepMode, prelude::*};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    asset_errors::AssetErrors,
+    asset_files::{parse_entries, Entries},
+    cauldron::CauldronSensor,
+    handles::Handles,
+    items::ItemBundle,
+    levels::{levels_loaded, Level, LevelFile},
+    replay::{HoofSample, Replay},
+    tools::ToolControl,
+    AppState, FailureCause, GameRng, HoofTarget, IsItem, ItemInCauldronEvent, LevelFailure,
+    LevelPlugin, LevelStats,
+};
+
+pub const DEFAULT_MAX_TIME: f32 = 20.;
+
+// An item is at rest when it moves slower than this...
+const REST_LINVEL: f32 = 1.;
+const REST_ANGVEL: f32 = 0.05;
+// ...and every item has been at rest for this long
+const REST_DURATION: f32 = 1.;
+// How long to keep simulating after a replay ends, for the last items to fall in
+const REPLAY_GRACE_TIME: f32 = 5.;
+// How far above a cauldron items are dropped into it
+const DROP_HEIGHT: f32 = 80.;
+// How long the assets of a level may take to load
+const LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A level of an assets folder, ready to be simulated.
+#[derive(Clone)]
+pub struct LevelSetup {
+    pub label: String,
+    assets: PathBuf,
+    /// The asset path of the level, such as `levels.levels#level1`.
+    path: String,
+}
+
+pub fn load_levels(assets: impl AsRef<Path>, file: &str) -> Result<Vec<LevelSetup>> {
+    let assets = assets.as_ref();
+    let assets = std::fs::canonicalize(assets)
+        .with_context(|| format!("could not find {}", assets.display()))?;
+    let path = assets.join(file);
+    let bytes =
+        std::fs::read(&path).with_context(|| format!("could not read {}", path.display()))?;
+    let levels = parse_entries::<LevelFile>(&bytes)
+        .with_context(|| format!("could not parse {}", path.display()))?;
+
+    // Labeled as `LevelLoader` labels them
+    Ok(match levels {
+        Entries::One(level) => vec![LevelSetup {
+            label: level.label,
+            assets,
+            path: file.to_string(),
+        }],
+        Entries::Many(levels) => levels
+            .into_iter()
+            .map(|level| LevelSetup {
+                path: format!("{}#{}", file, level.label),
+                label: level.label,
+                assets: assets.clone(),
+            })
+            .collect(),
+    })
+}
+
+/// The types of the items that fell into a cauldron.
+#[derive(Default)]
+struct InCauldron(Vec<String>);
+
+fn record_in_cauldron(
+    mut item_in_cauldron_events: EventReader<ItemInCauldronEvent>,
+    mut in_cauldron: ResMut<InCauldron>,
+) {
+    for ItemInCauldronEvent { ty, .. } in item_in_cauldron_events.iter() {
+        in_cauldron.0.push(ty.clone());
+    }
+}
+
+/// A level played by the game's own systems, without any window or renderer. Every
+/// update is one physics step.
+struct Simulation {
+    app: App,
+    /// Physics time into the attempt.
+    time: f32,
+}
+
+impl Simulation {
+    /// Loads `level` and everything it is made of, and starts it with the hoof steered
+    /// by `hoof`.
+    fn new(level: &LevelSetup, hoof: &HoofSample, seed: u64) -> Result<Self> {
+        let mut builder = App::build();
+        builder
+            .insert_resource(AssetServerSettings {
+                asset_folder: level.assets.to_string_lossy().into_owned(),
+            })
+            .add_plugins(MinimalPlugins)
+            .add_plugin(bevy::transform::TransformPlugin)
+            .add_plugin(bevy::asset::AssetPlugin)
+            // Items and the like refer to them, though nothing draws them here
+            .add_asset::<Texture>()
+            .add_asset::<TextureAtlas>()
+            .add_asset::<ColorMaterial>()
+            .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+            .add_plugin(LevelPlugin)
+            .init_resource::<InCauldron>()
+            .add_system(record_in_cauldron.system());
+        let mut simulation = Self {
+            app: std::mem::take(&mut builder.app),
+            time: 0.,
+        };
+
+        // Only the level being played, so that it is the current one
+        let world = &mut simulation.app.world;
+        let handle = world
+            .get_resource::<AssetServer>()
+            .unwrap()
+            .load(level.path.as_str());
+        world.get_resource_mut::<Handles>().unwrap().levels = vec![handle];
+
+        let started = Instant::now();
+        loop {
+            simulation.app.update();
+            let world = &simulation.app.world;
+            let errors = world.get_resource::<AssetErrors>().unwrap().messages();
+            if !errors.is_empty() {
+                bail!("{}: {}", level.label, errors.join("\n"));
+            }
+            let loaded = levels_loaded(
+                world.get_resource::<AssetServer>().unwrap(),
+                world.get_resource::<Handles>().unwrap(),
+                world.get_resource::<Assets<Level>>().unwrap(),
+                world.get_resource::<Assets<ItemBundle>>().unwrap(),
+            );
+            if loaded {
+                break;
+            }
+            if started.elapsed() > LOAD_TIMEOUT {
+                bail!("{}: could not load '{}'", level.label, level.path);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        simulation.steer(hoof);
+        let world = &mut simulation.app.world;
+        // One step of `IntegrationParameters::dt` per update, as the game takes at full
+        // frame rate
+        world
+            .get_resource_mut::<RapierConfiguration>()
+            .unwrap()
+            .timestep_mode = TimestepMode::FixedTimestep;
+        world.get_resource_mut::<GameRng>().unwrap().reseed(seed);
+        world
+            .get_resource_mut::<State<AppState>>()
+            .unwrap()
+            .set(AppState::InGame)?;
+
+        // Until the level is spawned
+        for _ in 0..10 {
+            simulation.app.update();
+            if simulation.stats().attempts > 0 {
+                return Ok(simulation);
+            }
+        }
+        bail!("{}: the level did not start", level.label);
+    }
+
+    /// Sets the hoof target and tool control, as `replay::replay_hoof` does.
+    fn steer(&mut self, sample: &HoofSample) {
+        let world = &mut self.app.world;
+        world.get_resource_mut::<HoofTarget>().unwrap().0 = sample.target;
+        let mut control = world.get_resource_mut::<ToolControl>().unwrap();
+        if !sample.tool.is_empty() {
+            control.tool = sample.tool.clone();
+        }
+        control.grab = sample.grab;
+    }
+
+    fn step(&mut self) {
+        self.app.update();
+        self.time += self
+            .app
+            .world
+            .get_resource::<IntegrationParameters>()
+            .unwrap()
+            .dt;
+    }
+
+    fn state(&self) -> &AppState {
+        self.app
+            .world
+            .get_resource::<State<AppState>>()
+            .unwrap()
+            .current()
+    }
+
+    fn stats(&self) -> &LevelStats {
+        self.app.world.get_resource::<LevelStats>().unwrap()
+    }
+
+    /// Why the level failed, if it did.
+    fn failure(&self) -> Option<Failure> {
+        if *self.state() != AppState::LevelFailed {
+            return None;
+        }
+        let failure = self.app.world.get_resource::<LevelFailure>().unwrap();
+        Some(match &failure.cause {
+            FailureCause::Dropped(ty) => Failure::WrongItem(failure.expected.clone(), ty.clone()),
+            FailureCause::Broken(ty) => Failure::Broken(ty.clone()),
+        })
+    }
+
+    fn is_at_rest(&mut self) -> bool {
+        let world = &mut self.app.world;
+        world
+            .query_filtered::<(&RigidBodyVelocity, &RigidBodyActivation), With<IsItem>>()
+            .iter(world)
+            .all(|(velocity, activation)| {
+                activation.sleeping
+                    || (velocity.linvel.norm() < REST_LINVEL && velocity.angvel.abs() < REST_ANGVEL)
+            })
+    }
+
+    /// Puts an item of type `ty` above the first cauldron, at rest, as if the player had
+    /// carried it there. Returns whether there was such an item.
+    fn place_above_cauldron(&mut self, ty: &str) -> bool {
+        let world = &mut self.app.world;
+        let above = match world
+            .query::<&CauldronSensor>()
+            .iter(world)
+            .find(|sensor| sensor.index == 0)
+        {
+            Some(sensor) => sensor.position + Vec2::new(0., DROP_HEIGHT),
+            None => return false,
+        };
+        let item = world
+            .query::<(&IsItem, &mut RigidBodyPosition, &mut RigidBodyVelocity)>()
+            .iter_mut(world)
+            .find(|(item, _, _)| item.0 == ty);
+        match item {
+            Some((_, mut position, mut velocity)) => {
+                position.position = Isometry::translation(above.x, above.y);
+                position.next_position = position.position;
+                *velocity = RigidBodyVelocity::default();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Plays until `end`, steering the hoof with `sample_at` the time of each step, until
+    /// the recipes are complete or the level fails.
+    fn play<'a>(
+        mut self,
+        level: &LevelSetup,
+        end: f32,
+        sample_at: impl Fn(f32) -> Option<&'a HoofSample>,
+    ) -> ReplayReport {
+        let mut report = ReplayReport {
+            level: level.label.clone(),
+            completed_at: None,
+            failure: None,
+        };
+
+        while self.time <= end {
+            if let Some(sample) = sample_at(self.time) {
+                self.steer(sample);
+            }
+            self.step();
+            match self.state() {
+                AppState::LevelComplete => {
+                    report.completed_at = Some(self.time);
+                    break;
+                }
+                AppState::LevelFailed => {
+                    report.failure = self.failure();
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        report
+    }
+}
+
+/// Where the hoof waits when nothing steers it, above the view, out of the way of items.
+fn parked_hoof() -> HoofSample {
+    HoofSample {
+        target: Vec2::new(0., 400.),
+        ..Default::default()
+    }
+}
+
+pub struct StabilityReport {
+    pub level: String,
+    pub max_time: f32,
+    /// When the items came to rest, if they did.
+    pub settle_time: Option<f32>,
+    /// The types of the items that fell into a cauldron.
+    pub in_cauldron: Vec<String>,
+    /// How many items fell out of bounds.
+    pub lost: u32,
+}
+
+impl StabilityReport {
+    pub fn is_stable(&self) -> bool {
+        self.settle_time.is_some() && self.in_cauldron.is_empty() && self.lost == 0
+    }
+}
+
+impl fmt::Display for StabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.level)?;
+        match self.settle_time {
+            Some(time) => write!(f, "settled after {:.2}s", time)?,
+            None => write!(f, "did not settle within {:.0}s", self.max_time)?,
+        }
+        if !self.in_cauldron.is_empty() {
+            write!(
+                f,
+                "; fell into the cauldron: {}",
+                self.in_cauldron.join(", ")
+            )?;
+        }
+        if self.lost > 0 {
+            write!(f, "; {} fell out of bounds", self.lost)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lets a level run without any input until its items settle or `max_time` runs out.
+pub fn check_stability(level: &LevelSetup, max_time: f32) -> Result<StabilityReport> {
+    let mut simulation = Simulation::new(level, &parked_hoof(), 0)?;
+
+    let mut settle_time = None;
+    let mut rest_since = None;
+    while simulation.time < max_time {
+        simulation.step();
+
+        if simulation.is_at_rest() {
+            let since = *rest_since.get_or_insert(simulation.time);
+            if simulation.time - since >= REST_DURATION {
+                settle_time = Some(since);
+                break;
+            }
+        } else {
+            rest_since = None;
+        }
+    }
+
+    let world = &simulation.app.world;
+    Ok(StabilityReport {
+        level: level.label.clone(),
+        max_time,
+        settle_time,
+        in_cauldron: world.get_resource::<InCauldron>().unwrap().0.clone(),
+        lost: simulation.stats().lost,
+    })
+}
+
+/// Why a level failed.
+pub enum Failure {
+    /// The types that were expected, and the one that fell in instead.
+    WrongItem(Vec<String>, String),
+    /// An item of that type broke, and the recipes could no longer be completed.
+    Broken(String),
+}
+
+pub struct ReplayReport {
+    pub level: String,
+    /// When the last ingredient of the recipes fell into a cauldron, if it did.
+    pub completed_at: Option<f32>,
+    pub failure: Option<Failure>,
+}
+
+impl ReplayReport {
+    pub fn is_completed(&self) -> bool {
+        self.completed_at.is_some()
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.level)?;
+        match (self.completed_at, &self.failure) {
+            (Some(time), _) => write!(f, "completed after {:.2}s", time),
+            (None, Some(Failure::WrongItem(expected, actual))) => {
+                write!(
+                    f,
+                    "expected '{}' but got '{}'",
+                    expected.join("' or '"),
+                    actual
+                )
+            }
+            (None, Some(Failure::Broken(ty))) => write!(f, "'{}' broke", ty),
+            (None, None) => write!(f, "recipe not completed"),
+        }
+    }
+}
+
+/// Drives the hoof along a recorded replay and checks whether it completes the recipes.
+pub fn play_replay(level: &LevelSetup, replay: &Replay) -> Result<ReplayReport> {
+    let start = replay.hoof.first().cloned().unwrap_or_else(parked_hoof);
+    let simulation = Simulation::new(level, &start, replay.seed)?;
+    // Past the end, the hoof stays where the replay left it
+    Ok(
+        simulation.play(level, replay.duration() + REPLAY_GRACE_TIME, |time| {
+            replay.sample_at(time)
+        }),
+    )
+}
+
+/// Lets a level run without any input, and checks what that does to the recipes.
+pub fn play_without_input(level: &LevelSetup, max_time: f32) -> Result<ReplayReport> {
+    let simulation = Simulation::new(level, &parked_hoof(), 0)?;
+    Ok(simulation.play(level, max_time, |_| None))
+}
+
+/// Drops an item of type `ty` into the first cauldron without any other input, and
+/// checks what that does to the recipes. `None` if the level has no such item.
+pub fn drop_in_cauldron(
+    level: &LevelSetup,
+    ty: &str,
+    max_time: f32,
+) -> Result<Option<ReplayReport>> {
+    let mut simulation = Simulation::new(level, &parked_hoof(), 0)?;
+    if !simulation.place_above_cauldron(ty) {
+        return Ok(None);
+    }
+    Ok(Some(simulation.play(level, max_time, |_| None)))
+}
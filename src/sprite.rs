@@ -0,0 +1,75 @@
+use anyhow::Result;
+use bevy::{
+    asset::{AssetPath, LoadContext},
+    ecs::system::EntityCommands,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::asset_files::set_labeled_asset;
+
+/// A whole image, one texture of an atlas, or a plain `(r, g, b)` rectangle of some size.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub(crate) enum SpriteFile {
+    Image(String),
+    Atlas(String, u32),
+    Rect((f32, f32, f32), Vec2),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum SpriteHandle {
+    Image(Handle<ColorMaterial>),
+    Atlas(Handle<TextureAtlas>, u32),
+    Rect(Handle<ColorMaterial>, Vec2),
+}
+
+/// Resolves a sprite, adding the material for an image or a rectangle as an asset
+/// labeled `label`.
+pub(crate) fn sprite_asset(
+    sprite: &SpriteFile,
+    label: &str,
+    load_context: &mut LoadContext,
+) -> Result<SpriteHandle> {
+    Ok(match sprite {
+        SpriteFile::Image(path) => {
+            let texture: Handle<Texture> =
+                load_context.get_handle(AssetPath::from(path.as_str()).get_id());
+            let material = ColorMaterial::texture(texture);
+            SpriteHandle::Image(set_labeled_asset(load_context, label, material)?)
+        }
+        SpriteFile::Atlas(path, index) => SpriteHandle::Atlas(
+            load_context.get_handle(AssetPath::from(path.as_str()).get_id()),
+            *index,
+        ),
+        SpriteFile::Rect((r, g, b), size) => {
+            let material = ColorMaterial::color(Color::rgb(*r, *g, *b));
+            SpriteHandle::Rect(set_labeled_asset(load_context, label, material)?, *size)
+        }
+    })
+}
+
+impl SpriteHandle {
+    pub fn insert(&self, commands: &mut EntityCommands, transform: Transform) {
+        match self {
+            SpriteHandle::Image(material) => commands.insert_bundle(SpriteBundle {
+                transform,
+                material: material.clone(),
+                ..Default::default()
+            }),
+            SpriteHandle::Atlas(texture_atlas, texture_index) => {
+                commands.insert_bundle(SpriteSheetBundle {
+                    sprite: TextureAtlasSprite::new(*texture_index),
+                    texture_atlas: texture_atlas.clone(),
+                    transform,
+                    ..Default::default()
+                })
+            }
+            SpriteHandle::Rect(material, size) => commands.insert_bundle(SpriteBundle {
+                sprite: Sprite::new(*size),
+                transform,
+                material: material.clone(),
+                ..Default::default()
+            }),
+        };
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::Result;
+
+/// Somewhere to keep small named blobs of text between runs.
+pub trait Storage: Send + Sync {
+    fn load(&self, key: &str) -> Option<String>;
+    fn save(&self, key: &str, value: &str) -> Result<()>;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn default_storage() -> Box<dyn Storage> {
+    match dirs::data_dir() {
+        Some(dir) => Box::new(FileStorage {
+            dir: dir.join("chevalchemy"),
+        }),
+        None => {
+            eprintln!("error: could not find a data directory, progress will not be saved");
+            Box::new(NoStorage)
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn default_storage() -> Box<dyn Storage> {
+    Box::new(crate::wasm::LocalStorage)
+}
+
+/// One RON file per key, in the user's data directory.
+#[cfg(not(target_arch = "wasm32"))]
+struct FileStorage {
+    dir: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage for FileStorage {
+    fn load(&self, key: &str) -> Option<String> {
+        std::fs::read_to_string(self.dir.join(key).with_extension("ron")).ok()
+    }
+
+    fn save(&self, key: &str, value: &str) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.dir.join(key).with_extension("ron"), value)?;
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct NoStorage;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage for NoStorage {
+    fn load(&self, _key: &str) -> Option<String> {
+        None
+    }
+
+    fn save(&self, _key: &str, _value: &str) -> Result<()> {
+        Ok(())
+    }
+}
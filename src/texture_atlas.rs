@@ -1,6 +1,7 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use bevy::{
-    asset::{AssetLoader, AssetPath, LoadedAsset},
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    ecs::world::FromWorld,
     prelude::*,
     sprite,
     utils::BoxedFuture,
@@ -9,6 +10,8 @@ use serde::{Deserialize, Serialize};
 
 use std::path::{Path, PathBuf};
 
+use crate::asset_errors::AssetErrors;
+
 #[derive(Deserialize, Serialize)]
 struct SpriteRect {
     min: Vec2,
@@ -57,9 +60,21 @@ pub struct TextureAtlasFile {
     #[serde(serialize_with = "sprite_rect_vec_ser")]
     #[serde(deserialize_with = "sprite_rect_vec_deser")]
     textures: Vec<sprite::Rect>,
+    /// The name of each texture, for tools that need to keep indices stable.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    names: Vec<String>,
 }
 
 impl TextureAtlasFile {
+    pub fn new(src: PathBuf, size: Vec2, textures: Vec<sprite::Rect>, names: Vec<String>) -> Self {
+        Self {
+            src,
+            size,
+            textures,
+            names,
+        }
+    }
+
     pub fn from_file<'a>(path: impl AsRef<Path>) -> Result<Self, ron::Error> {
         let f = std::fs::File::open(path).map_err(|e| ron::Error {
             code: ron::error::ErrorCode::Io(e.to_string()),
@@ -68,6 +83,44 @@ impl TextureAtlasFile {
         ron::de::from_reader(f)
     }
 
+    pub fn src(&self) -> &Path {
+        &self.src
+    }
+
+    /// Where the image is, given where the atlas is, since `src` is relative to the atlas.
+    pub fn src_next_to(&self, atlas: &Path) -> PathBuf {
+        atlas
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(&self.src)
+    }
+
+    /// Catches names that don't line up with the textures.
+    pub fn check(&self) -> Result<(), String> {
+        if !self.names.is_empty() && self.names.len() != self.textures.len() {
+            return Err(format!(
+                "{} texture names for {} textures",
+                self.names.len(),
+                self.textures.len()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn texture_count(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let atlas = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())?;
+        std::fs::write(path, atlas)?;
+        Ok(())
+    }
+
     pub fn into_asset(self, asset_server: &AssetServer) -> TextureAtlas {
         let texture = asset_server.load(self.src);
         let mut atlas = TextureAtlas::new_empty(texture, self.size);
@@ -78,8 +131,17 @@ impl TextureAtlasFile {
     }
 }
 
-#[derive(Clone, Default)]
-pub struct TextureAtlasLoader;
+pub struct TextureAtlasLoader {
+    errors: AssetErrors,
+}
+
+impl FromWorld for TextureAtlasLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            errors: world.get_resource::<AssetErrors>().unwrap().clone(),
+        }
+    }
+}
 
 const FILE_EXTENSIONS: &[&str] = &["atlas"];
 
@@ -90,18 +152,9 @@ impl AssetLoader for TextureAtlasLoader {
         load_context: &'a mut bevy::asset::LoadContext<'_>,
     ) -> BoxedFuture<'a, Result<()>> {
         Box::pin(async move {
-            let atlas: TextureAtlasFile = ron::de::from_bytes(bytes)?;
-
-            let texture: Handle<Texture> =
-                load_context.get_handle(AssetPath::from(atlas.src).get_id());
-
-            let mut asset = TextureAtlas::new_empty(texture, atlas.size);
-            for rect in atlas.textures {
-                asset.add_texture(rect);
-            }
-
-            load_context.set_default_asset(LoadedAsset::new(asset));
-            Ok(())
+            let result = load_texture_atlas(bytes, load_context);
+            self.errors.report(load_context.path(), &result);
+            result
         })
     }
 
@@ -109,3 +162,19 @@ impl AssetLoader for TextureAtlasLoader {
         FILE_EXTENSIONS
     }
 }
+
+fn load_texture_atlas(bytes: &[u8], load_context: &mut LoadContext) -> Result<()> {
+    let atlas: TextureAtlasFile = ron::de::from_bytes(bytes)?;
+    atlas.check().map_err(|e| anyhow!(e))?;
+
+    let src = atlas.src_next_to(load_context.path());
+    let texture: Handle<Texture> = load_context.get_handle(AssetPath::from(src).get_id());
+
+    let mut asset = TextureAtlas::new_empty(texture, atlas.size);
+    for rect in atlas.textures {
+        asset.add_texture(rect);
+    }
+
+    load_context.set_default_asset(LoadedAsset::new(asset));
+    Ok(())
+}
@@ -0,0 +1,388 @@
+use anyhow::Result;
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::world::FromWorld,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asset_errors::AssetErrors,
+    asset_files::parse_entries,
+    handles::Handles,
+    input::Action,
+    items::{check_shapes, insert_colliders, Collider},
+    levels::Level,
+    material::Material,
+    sprite::{sprite_asset, SpriteFile, SpriteHandle},
+    CurrentLevel, HoofSettings, HoofTarget, IsItem, LevelEntity,
+};
+
+/// The file that holds every tool, in the order they are picked with the number keys.
+pub(crate) const TOOLS_FILE: &str = "tools.tools";
+
+const TOOL_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+/// What the player moves items around with, such as the hoof.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct ToolFile {
+    pub label: String,
+    #[serde(default, skip_serializing_if = "Material::is_empty")]
+    pub material: Material,
+    pub colliders: Vec<Collider>,
+    /// Drawn above everything else, `sprite_offset` away from the tool.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sprite: Option<SpriteFile>,
+    #[serde(default)]
+    pub sprite_offset: Vec2,
+    #[serde(default)]
+    pub behavior: Behavior,
+    /// Units per second, the hoof's if left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_speed: Option<f32>,
+    /// Units per second squared, the hoof's if left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_acceleration: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub(crate) enum Behavior {
+    /// Pushes items around.
+    Push,
+    /// Also holds on to an item it touches while the button is held.
+    Grab,
+}
+
+impl Default for Behavior {
+    fn default() -> Self {
+        Self::Push
+    }
+}
+
+impl ToolFile {
+    pub fn settings(&self) -> HoofSettings {
+        let default = HoofSettings::default();
+        HoofSettings {
+            max_speed: self.max_speed.unwrap_or(default.max_speed),
+            max_acceleration: self.max_acceleration.unwrap_or(default.max_acceleration),
+        }
+    }
+
+    /// Catches speeds that would keep the tool from moving.
+    pub fn check_settings(&self) -> Result<(), String> {
+        let settings = self.settings();
+        if settings.max_speed <= 0. {
+            return Err(format!("max_speed {} is not positive", settings.max_speed));
+        }
+        if settings.max_acceleration <= 0. {
+            return Err(format!(
+                "max_acceleration {} is not positive",
+                settings.max_acceleration
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tool {
+    pub(crate) file: ToolFile,
+    sprite: Option<SpriteHandle>,
+}
+
+#[derive(Debug, Clone, Default, TypeUuid)]
+#[uuid = "5c0a8e2b-7f41-4d93-9b6e-2a8d1f3c6e05"]
+pub struct Tools(pub Vec<Tool>);
+
+impl Tools {
+    /// The tools a level lets the player use, all of them if it doesn't say.
+    pub fn available(&self, level: &Level) -> Vec<&Tool> {
+        self.0
+            .iter()
+            .filter(|tool| level.tools.is_empty() || level.tools.contains(&tool.file.label))
+            .collect()
+    }
+}
+
+/// Which tool the player asked for and whether they are grabbing, recorded in replays
+/// along with the hoof target.
+#[derive(Default)]
+pub struct ToolControl {
+    /// The label of the tool to hold, the first one the level allows if it doesn't.
+    pub tool: String,
+    /// Whether `Action::Grab` is held.
+    pub grab: bool,
+}
+
+/// The tool in play, which follows the hoof target.
+pub struct HeldTool {
+    pub label: String,
+    pub settings: HoofSettings,
+    behavior: Behavior,
+    /// The joint holding on to an item, if any.
+    grabbed: Option<Entity>,
+}
+
+impl Tool {
+    pub fn spawn(&self, commands: &mut Commands, position: Vec2) {
+        let file = &self.file;
+        let mut entity = commands.spawn_bundle((Transform::default(), GlobalTransform::default()));
+        entity
+            .insert_bundle(RigidBodyBundle {
+                body_type: RigidBodyType::KinematicVelocityBased,
+                position: position.into(),
+                ccd: RigidBodyCcd {
+                    ccd_enabled: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(RigidBodyPositionSync::Discrete)
+            .insert(HeldTool {
+                label: file.label.clone(),
+                settings: file.settings(),
+                behavior: file.behavior,
+                grabbed: None,
+            });
+        insert_colliders(&mut entity, &file.colliders, &file.material);
+        if let Some(sprite) = &self.sprite {
+            let transform = Transform::from_translation(file.sprite_offset.extend(500.));
+            entity.with_children(|parent| sprite.insert(&mut parent.spawn(), transform));
+        }
+    }
+}
+
+/// Picks a tool with the number keys, or the next one with `Action::NextTool`, among
+/// those the level allows.
+pub fn pick_tool(
+    keys: Res<Input<KeyCode>>,
+    actions: Res<Input<Action>>,
+    mut control: ResMut<ToolControl>,
+    current_level: Res<CurrentLevel>,
+    handles: Res<Handles>,
+    levels: Res<Assets<Level>>,
+    tools: Res<Assets<Tools>>,
+) {
+    control.grab = actions.pressed(Action::Grab);
+
+    let (level, tools) = match (
+        levels.get(&handles.levels[current_level.0]),
+        tools.get(&handles.tools),
+    ) {
+        (Some(level), Some(tools)) => (level, tools),
+        _ => return,
+    };
+    let available = tools.available(level);
+    if available.is_empty() {
+        return;
+    }
+
+    let current = available
+        .iter()
+        .position(|tool| tool.file.label == control.tool);
+    let mut selected = current.unwrap_or(0);
+    for (i, key) in TOOL_KEYS.iter().enumerate().take(available.len()) {
+        if keys.just_pressed(*key) {
+            selected = i;
+        }
+    }
+    if actions.just_pressed(Action::NextTool) {
+        selected = (selected + 1) % available.len();
+    }
+    if current != Some(selected) {
+        control.tool = available[selected].file.label.clone();
+    }
+}
+
+/// Swaps the tool in play for the one asked for when it changes or is reloaded.
+pub fn switch_tool(
+    mut commands: Commands,
+    mut control: ResMut<ToolControl>,
+    hoof_target: Res<HoofTarget>,
+    current_level: Res<CurrentLevel>,
+    handles: Res<Handles>,
+    levels: Res<Assets<Level>>,
+    tools: Res<Assets<Tools>>,
+    mut tool_events: EventReader<AssetEvent<Tools>>,
+    held: Query<(Entity, &HeldTool, &RigidBodyPosition)>,
+) {
+    // A reloaded tool is swapped for its new self
+    let reloaded = tool_events
+        .iter()
+        .any(|event| matches!(event, AssetEvent::Modified { .. }));
+
+    let (level, tools) = match (
+        levels.get(&handles.levels[current_level.0]),
+        tools.get(&handles.tools),
+    ) {
+        (Some(level), Some(tools)) => (level, tools),
+        _ => return,
+    };
+    let available = tools.available(level);
+    if available.is_empty() {
+        return;
+    }
+
+    // Falls back on the first tool if the one asked for isn't allowed here
+    let selected = available
+        .iter()
+        .position(|tool| tool.file.label == control.tool)
+        .unwrap_or(0);
+    if available[selected].file.label != control.tool {
+        control.tool = available[selected].file.label.clone();
+    }
+
+    let held = held.single().ok();
+    if held.map_or(false, |(_, held, _)| held.label == control.tool) && !reloaded {
+        return;
+    }
+
+    // The new tool takes over where the old one was
+    let position = match held {
+        Some((entity, held, position)) => {
+            if let Some(joint) = held.grabbed {
+                commands.entity(joint).despawn();
+            }
+            commands.entity(entity).despawn_recursive();
+            let translation = position.position.translation;
+            Vec2::new(translation.x, translation.y)
+        }
+        None => hoof_target.0,
+    };
+    available[selected].spawn(&mut commands, position);
+}
+
+/// Holds on to an item the tool touches when grabbing starts, until it stops, for
+/// tools that grab.
+pub fn grab(
+    mut commands: Commands,
+    control: Res<ToolControl>,
+    mut was_grabbing: Local<bool>,
+    narrow_phase: Res<NarrowPhase>,
+    mut held: Query<(Entity, &mut HeldTool, &RigidBodyPosition, Option<&Children>)>,
+    items: Query<&RigidBodyPosition, With<IsItem>>,
+    parents: Query<&Parent>,
+    entities: Query<Entity>,
+) {
+    let pressed = control.grab && !*was_grabbing;
+    *was_grabbing = control.grab;
+
+    let (tool, mut held, position, children) = match held.single_mut() {
+        Ok(held) => held,
+        Err(_) => return,
+    };
+
+    // The joint is gone along with the rest of the level when it is reset
+    if let Some(joint) = held.grabbed {
+        if entities.get(joint).is_err() {
+            held.grabbed = None;
+        }
+    }
+    if !control.grab {
+        if let Some(joint) = held.grabbed.take() {
+            commands.entity(joint).despawn();
+        }
+    }
+    if held.behavior != Behavior::Grab || held.grabbed.is_some() || !pressed {
+        return;
+    }
+
+    // Colliders with their own material are children of the tool, as with items
+    let colliders =
+        std::iter::once(tool).chain(children.into_iter().flat_map(|c| c.iter().copied()));
+    let touched = colliders
+        .flat_map(|collider| {
+            let handle = collider.handle();
+            narrow_phase
+                .contacts_with(handle)
+                .filter(|pair| pair.has_any_active_contact)
+                .map(move |pair| {
+                    if pair.collider1 == handle {
+                        pair.collider2
+                    } else {
+                        pair.collider1
+                    }
+                })
+        })
+        .map(|other| {
+            let other = other.entity();
+            parents.get(other).map_or(other, |parent| parent.0)
+        })
+        .find_map(|other| items.get(other).ok().map(|item| (other, item)));
+
+    if let Some((item, item_position)) = touched {
+        // Holds the item just as it is
+        let frame = position.position.inverse() * item_position.position;
+        let joint = FixedJoint::new(frame, Isometry::identity());
+        let joint = commands
+            .spawn()
+            .insert(JointBuilderComponent::new(joint, tool, item))
+            .insert(LevelEntity)
+            .id();
+        held.grabbed = Some(joint);
+    }
+}
+
+pub struct ToolsLoader {
+    errors: AssetErrors,
+}
+
+impl FromWorld for ToolsLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            errors: world.get_resource::<AssetErrors>().unwrap().clone(),
+        }
+    }
+}
+
+const FILE_EXTENSIONS: &[&str] = &["tools"];
+
+impl AssetLoader for ToolsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext<'_>,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let result = load_tools(bytes, load_context);
+            self.errors.report(load_context.path(), &result);
+            result
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        FILE_EXTENSIONS
+    }
+}
+
+fn load_tools(bytes: &[u8], load_context: &mut LoadContext) -> Result<()> {
+    let tools = parse_entries::<ToolFile>(bytes)?
+        .into_vec()
+        .into_iter()
+        .map(|tool| {
+            check_shapes(tool.colliders.iter().map(|c| &c.shape), &tool.label)?;
+            let sprite = tool
+                .sprite
+                .as_ref()
+                .map(|sprite| sprite_asset(sprite, &tool.label, load_context))
+                .transpose()?;
+            Ok(Tool { file: tool, sprite })
+        })
+        .collect::<Result<_>>()?;
+
+    load_context.set_default_asset(LoadedAsset::new(Tools(tools)));
+    Ok(())
+}
@@ -1,6 +1,13 @@
-use bevy::prelude::*;
+use anyhow::{anyhow, Result};
+use bevy::{
+    input::{mouse::MouseButtonInput, ElementState, InputSystem},
+    prelude::*,
+};
+use std::cell::RefCell;
 use wasm_bindgen::prelude::*;
 
+use crate::storage::Storage;
+
 pub struct WasmPlugin;
 
 impl Plugin for WasmPlugin {
@@ -10,13 +17,40 @@ impl Plugin for WasmPlugin {
         app.add_plugin(bevy_webgl2::WebGL2Plugin)
             // "resizer" hack to ensure the canvas size is correct,
             // ruthlessly stolen from https://github.com/horup/some-tank-game-rs
-            .add_system(resizer.system());
+            .add_system(resizer.system())
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                touch_as_mouse.system().before(InputSystem),
+            );
     }
 }
 
 #[wasm_bindgen]
 extern "C" {
     fn resize_canvas(width: f32, height: f32);
+
+    #[wasm_bindgen(catch, js_namespace = localStorage, js_name = getItem)]
+    fn local_storage_get(key: &str) -> Result<Option<String>, JsValue>;
+
+    #[wasm_bindgen(catch, js_namespace = localStorage, js_name = setItem)]
+    fn local_storage_set(key: &str, value: &str) -> Result<(), JsValue>;
+}
+
+/// The browser's localStorage, with keys prefixed by the game's name.
+pub struct LocalStorage;
+
+impl Storage for LocalStorage {
+    fn load(&self, key: &str) -> Option<String> {
+        // Browsers that block storage throw, which is the same as having nothing saved
+        local_storage_get(&format!("chevalchemy.{}", key))
+            .ok()
+            .flatten()
+    }
+
+    fn save(&self, key: &str, value: &str) -> Result<()> {
+        local_storage_set(&format!("chevalchemy.{}", key), value)
+            .map_err(|e| anyhow!("could not write to localStorage: {:?}", e))
+    }
 }
 
 pub fn resizer(
@@ -46,3 +80,50 @@ pub fn resizer(
         }
     }
 }
+
+thread_local! {
+    /// Touches from the page, as the event type and where on the canvas they were, from
+    /// `(0, 0)` at the top left to `(1, 1)`.
+    static TOUCHES: RefCell<Vec<(String, Vec2)>> = RefCell::new(Vec::new());
+}
+
+/// Called by the page for every `touchstart`, `touchmove`, `touchend` and `touchcancel`
+/// on the canvas.
+#[wasm_bindgen]
+pub fn touch(event: String, x: f32, y: f32) {
+    TOUCHES.with(|touches| touches.borrow_mut().push((event, Vec2::new(x, y))));
+}
+
+/// Plays touches as the left mouse button, so that the game and its menus work on
+/// phones and tablets.
+fn touch_as_mouse(
+    mut windows: ResMut<Windows>,
+    mut cursor_moved_events: EventWriter<CursorMoved>,
+    mut mouse_button_events: EventWriter<MouseButtonInput>,
+) {
+    let window = match windows.get_primary_mut() {
+        Some(window) => window,
+        None => return,
+    };
+    TOUCHES.with(|touches| {
+        for (event, at) in touches.borrow_mut().drain(..) {
+            // The page measures from the top
+            let position = Vec2::new(at.x * window.width(), (1. - at.y) * window.height());
+            window.update_cursor_position_from_backend(Some(position));
+            cursor_moved_events.send(CursorMoved {
+                id: window.id(),
+                position,
+            });
+
+            let state = match event.as_str() {
+                "touchstart" => ElementState::Pressed,
+                "touchend" | "touchcancel" => ElementState::Released,
+                _ => continue,
+            };
+            mouse_button_events.send(MouseButtonInput {
+                button: MouseButton::Left,
+                state,
+            });
+        }
+    });
+}
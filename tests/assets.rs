@@ -0,0 +1,11 @@
+use chevalchemy::check::check_assets;
+
+#[test]
+fn assets_are_valid() {
+    let problems = check_assets(concat!(env!("CARGO_MANIFEST_DIR"), "/assets"))
+        .iter()
+        .map(|problem| problem.to_string())
+        .collect::<Vec<_>>();
+
+    assert!(problems.is_empty(), "problems:\n{}", problems.join("\n"));
+}
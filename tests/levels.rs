@@ -0,0 +1,78 @@
+use chevalchemy::simulation::{
+    check_stability, drop_in_cauldron, load_levels, play_without_input, Failure, LevelSetup,
+    DEFAULT_MAX_TIME,
+};
+
+fn levels() -> Vec<LevelSetup> {
+    load_levels(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/assets"),
+        "levels.levels",
+    )
+    .expect("could not load levels")
+}
+
+#[test]
+fn levels_settle_without_input() {
+    let unstable = levels()
+        .iter()
+        .map(|level| check_stability(level, DEFAULT_MAX_TIME).expect("could not simulate"))
+        .filter(|report| !report.is_stable())
+        .map(|report| report.to_string())
+        .collect::<Vec<_>>();
+
+    assert!(
+        unstable.is_empty(),
+        "unstable levels:\n{}",
+        unstable.join("\n")
+    );
+}
+
+#[test]
+fn yorick_is_forgiven() {
+    // Neither the skull nor the bone it boils down to fail the level
+    let failed = levels()
+        .iter()
+        .filter_map(|level| {
+            drop_in_cauldron(level, "yorick", DEFAULT_MAX_TIME).expect("could not simulate")
+        })
+        .filter(|report| report.failure.is_some())
+        .map(|report| report.to_string())
+        .collect::<Vec<_>>();
+
+    assert!(failed.is_empty(), "failed levels:\n{}", failed.join("\n"));
+}
+
+#[test]
+fn dropped_vial_breaks() {
+    let levels = load_levels(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/assets/breakable"),
+        "breakable.levels",
+    )
+    .expect("could not load levels");
+
+    let report = play_without_input(&levels[0], DEFAULT_MAX_TIME).expect("could not simulate");
+    assert!(
+        matches!(&report.failure, Some(Failure::Broken(ty)) if ty == "vial"),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn duplicate_labels_are_rejected() {
+    let levels = load_levels(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/assets/duplicate"),
+        "duplicate.levels",
+    )
+    .expect("could not load levels");
+
+    match play_without_input(&levels[0], DEFAULT_MAX_TIME) {
+        Ok(report) => panic!("loaded two bundles labeled 'vial': {}", report),
+        Err(e) => assert!(
+            e.to_string()
+                .contains("more than one asset is labeled 'vial"),
+            "{:#}",
+            e
+        ),
+    }
+}